@@ -0,0 +1,485 @@
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use bytemuck::Pod;
+use thiserror::Error;
+
+use crate::memory::ProcessArchitecture;
+
+#[derive(Error, Debug)]
+pub enum ElfError {
+    #[error("An I/O error occurred")]
+    Io(#[from] std::io::Error),
+    #[error("The file is not an ELF file")]
+    NotElf,
+    #[error("The file is big-endian, which is not supported")]
+    BigEndian,
+    #[error("The file is truncated or points outside of itself")]
+    OutOfBounds,
+}
+
+pub type Result<T> = std::result::Result<T, ElfError>;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_DYNSYM: u32 = 11;
+/// Sections such as `.bss` that only exist in memory
+const SHT_NOBITS: u32 = 8;
+const PT_LOAD: u32 = 1;
+const SHN_UNDEF: u16 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+/// A function whose address is picked at load time by calling it, such as `memcpy`
+const STT_GNU_IFUNC: u8 = 10;
+
+const PAGE_SIZE: u64 = 0x1000;
+/// Where distributions install the debug files they split off
+const DEBUG_DIRECTORY: &str = "/usr/lib/debug";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Object,
+    Function,
+    /// A resolver that returns the address of the function to use
+    IndirectFunction,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// The virtual address of the symbol, before the file is relocated
+    pub value: u64,
+    pub size: u64,
+    pub kind: SymbolKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub kind: u32,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    /// The index of a related section, such as the string table of a symbol table
+    pub link: u32,
+    pub entry_size: u64,
+}
+
+/// A part of the file that is mapped into memory when it is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub virtual_address: u64,
+    pub offset: u64,
+    pub file_size: u64,
+    pub memory_size: u64,
+    pub flags: u32,
+}
+
+/// An executable or shared library, parsed without loading it
+pub struct ElfImage {
+    data: Vec<u8>,
+    pub arch: ProcessArchitecture,
+    /// `ET_EXEC` for executables at a fixed address, `ET_DYN` for shared libraries and PIEs
+    pub kind: u16,
+    pub machine: u16,
+    pub entry_point: u64,
+    sections: Vec<Section>,
+    segments: Vec<Segment>,
+}
+
+impl ElfImage {
+    pub fn open(path: impl AsRef<Path>) -> Result<ElfImage> {
+        ElfImage::parse(std::fs::read(path)?)
+    }
+
+    pub fn parse(data: Vec<u8>) -> Result<ElfImage> {
+        let arch = match data.get(..6) {
+            Some([0x7F, b'E', b'L', b'F', 1, 1]) => ProcessArchitecture::X86,
+            Some([0x7F, b'E', b'L', b'F', 2, 1]) => ProcessArchitecture::X64,
+            Some([0x7F, b'E', b'L', b'F', _, 2]) => return Err(ElfError::BigEndian),
+            _ => return Err(ElfError::NotElf),
+        };
+        let reader = Reader { data: &data, arch };
+
+        // The fields after the entry point are shifted by the size of a word
+        let word = arch.pointer_size();
+        let entry_point = reader.word(24)?;
+        let program_headers = reader.word(24 + word)? as usize;
+        let section_headers = reader.word(24 + word * 2)? as usize;
+        let sizes = 24 + word * 3 + 4;
+
+        let program_header_size = reader.read::<u16>(sizes + 2)? as usize;
+        let program_header_count = reader.read::<u16>(sizes + 4)? as usize;
+        let section_header_size = reader.read::<u16>(sizes + 6)? as usize;
+        let section_header_count = reader.read::<u16>(sizes + 8)? as usize;
+        let section_names = reader.read::<u16>(sizes + 10)? as usize;
+
+        let mut segments = Vec::new();
+        for i in 0..program_header_count {
+            let header = program_headers + i * program_header_size;
+            if reader.read::<u32>(header)? != PT_LOAD {
+                continue;
+            }
+
+            // The flags come second in 64-bit files and next to last in 32-bit ones
+            segments.push(match arch {
+                ProcessArchitecture::X86 => Segment {
+                    offset: reader.word(header + 4)?,
+                    virtual_address: reader.word(header + 8)?,
+                    file_size: reader.word(header + 16)?,
+                    memory_size: reader.word(header + 20)?,
+                    flags: reader.read::<u32>(header + 24)?,
+                },
+                ProcessArchitecture::X64 => Segment {
+                    flags: reader.read::<u32>(header + 4)?,
+                    offset: reader.word(header + 8)?,
+                    virtual_address: reader.word(header + 16)?,
+                    file_size: reader.word(header + 32)?,
+                    memory_size: reader.word(header + 40)?,
+                },
+            });
+        }
+
+        let mut sections = (0..section_header_count)
+            .map(|i| {
+                let header = section_headers + i * section_header_size;
+                let name = reader.read::<u32>(header)?;
+
+                Ok((
+                    name,
+                    Section {
+                        name: String::new(),
+                        kind: reader.read::<u32>(header + 4)?,
+                        address: reader.word(header + 8 + word)?,
+                        offset: reader.word(header + 8 + word * 2)?,
+                        size: reader.word(header + 8 + word * 3)?,
+                        link: reader.read::<u32>(header + 8 + word * 4)?,
+                        entry_size: reader.word(header + 16 + word * 5)?,
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some((_, names)) = sections.get(section_names) {
+            let names = section_data(&data, names)?.to_vec();
+            for (name, section) in &mut sections {
+                section.name = read_string(&names, *name as usize).unwrap_or_default();
+            }
+        }
+
+        Ok(ElfImage {
+            arch,
+            kind: reader.read::<u16>(16)?,
+            machine: reader.read::<u16>(18)?,
+            entry_point,
+            sections: sections.into_iter().map(|(_, section)| section).collect(),
+            segments,
+            data,
+        })
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name == name)
+    }
+
+    /// Returns the contents of a section that is stored in the file
+    pub fn section_data(&self, section: &Section) -> Result<&[u8]> {
+        section_data(&self.data, section)
+    }
+
+    /// Returns the virtual address the start of the file is mapped at, which is what the base of
+    /// the module corresponds to once it is loaded
+    pub fn load_address(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| segment.virtual_address.wrapping_sub(segment.offset))
+            .min()
+            .unwrap_or_default()
+            & !(PAGE_SIZE - 1)
+    }
+
+    /// Returns the symbols defined by the file, from both the full symbol table and the dynamic
+    /// one that is kept when the file is stripped
+    pub fn symbols(&self) -> Result<Vec<Symbol>> {
+        let mut symbols = Vec::new();
+
+        for table in &self.sections {
+            if !matches!(table.kind, SHT_SYMTAB | SHT_DYNSYM) {
+                continue;
+            }
+            let names = self
+                .sections
+                .get(table.link as usize)
+                .ok_or(ElfError::OutOfBounds)?;
+            let names = self.section_data(names)?;
+            let entries = Reader {
+                data: self.section_data(table)?,
+                arch: self.arch,
+            };
+
+            let entry_size = match self.arch {
+                ProcessArchitecture::X86 => 16,
+                ProcessArchitecture::X64 => 24,
+            };
+
+            for offset in (0..entries.data.len() / entry_size).map(|i| i * entry_size) {
+                let (info, section, value, size) = match self.arch {
+                    ProcessArchitecture::X86 => (
+                        entries.read::<u8>(offset + 12)?,
+                        entries.read::<u16>(offset + 14)?,
+                        entries.word(offset + 4)?,
+                        entries.word(offset + 8)?,
+                    ),
+                    ProcessArchitecture::X64 => (
+                        entries.read::<u8>(offset + 4)?,
+                        entries.read::<u16>(offset + 6)?,
+                        entries.word(offset + 8)?,
+                        entries.word(offset + 16)?,
+                    ),
+                };
+
+                // Undefined symbols are imported from another module
+                if section == SHN_UNDEF {
+                    continue;
+                }
+                let Some(name) = read_string(names, entries.read::<u32>(offset)? as usize)
+                    .filter(|name| !name.is_empty())
+                else {
+                    continue;
+                };
+
+                symbols.push(Symbol {
+                    name,
+                    value,
+                    size,
+                    kind: match info & 0xF {
+                        STT_OBJECT => SymbolKind::Object,
+                        STT_FUNC => SymbolKind::Function,
+                        STT_GNU_IFUNC => SymbolKind::IndirectFunction,
+                        kind => SymbolKind::Other(kind),
+                    },
+                });
+            }
+        }
+
+        Ok(symbols)
+    }
+
+    /// Returns a defined symbol by its name, which may also be given without the version that
+    /// follows an `@`
+    pub fn symbol(&self, name: &str) -> Result<Option<Symbol>> {
+        let symbols = self.symbols()?;
+
+        let exact = symbols.iter().position(|symbol| symbol.name == name);
+        let unversioned = || {
+            symbols
+                .iter()
+                .position(|symbol| symbol.name.split('@').next() == Some(name))
+        };
+
+        Ok(exact.or_else(unversioned).map(|i| symbols[i].clone()))
+    }
+
+    /// Returns the name and the CRC32 of the file the debug information was split into
+    pub fn debug_link(&self) -> Option<(String, u32)> {
+        let link = self.section_data(self.section(".gnu_debuglink")?).ok()?;
+        let name = read_string(link, 0)?;
+
+        // The checksum follows the name, aligned to 4 bytes
+        let offset = (name.len() + 1).next_multiple_of(4);
+        let crc = link.get(offset..offset + 4)?;
+
+        Some((name, u32::from_le_bytes(crc.try_into().ok()?)))
+    }
+}
+
+/// Finds the file the debug information of the ELF file at `path` was split into, looking in the
+/// same places as gdb
+pub fn find_debug_file(path: &Path, image: &ElfImage) -> Option<PathBuf> {
+    let (name, crc) = image.debug_link()?;
+    let path = path.canonicalize().ok()?;
+    let directory = path.parent()?;
+
+    let candidates = [
+        directory.join(&name),
+        directory.join(".debug").join(&name),
+        Path::new(DEBUG_DIRECTORY)
+            .join(directory.strip_prefix("/").ok()?)
+            .join(&name),
+    ];
+
+    // The file itself may be named in its own link
+    candidates.into_iter().find(|candidate| {
+        *candidate != path
+            && std::fs::read(candidate).is_ok_and(|debug_file| crc32(&debug_file) == crc)
+    })
+}
+
+/// Looks up a symbol in the ELF file at `path`, or in its debug file if the file does not have
+/// it, and returns its offset from the base of the module once loaded
+pub fn symbol_offset(path: &Path, name: &str) -> Result<Option<u64>> {
+    let image = ElfImage::open(path)?;
+
+    let symbol = match image.symbol(name)? {
+        Some(symbol) => Some(symbol),
+        None => match find_debug_file(path, &image) {
+            Some(debug_file) => ElfImage::open(debug_file)?.symbol(name)?,
+            None => None,
+        },
+    };
+
+    // The debug file has the same layout, so the load address of the file applies to both
+    Ok(symbol.map(|symbol| symbol.value.wrapping_sub(image.load_address())))
+}
+
+fn section_data<'a>(data: &'a [u8], section: &Section) -> Result<&'a [u8]> {
+    if section.kind == SHT_NOBITS {
+        return Ok(&[]);
+    }
+
+    let start = usize::try_from(section.offset).map_err(|_| ElfError::OutOfBounds)?;
+    let end = start
+        .checked_add(usize::try_from(section.size).map_err(|_| ElfError::OutOfBounds)?)
+        .ok_or(ElfError::OutOfBounds)?;
+
+    data.get(start..end).ok_or(ElfError::OutOfBounds)
+}
+
+/// Reads a NUL-terminated string from a string table
+fn read_string(table: &[u8], offset: usize) -> Option<String> {
+    let bytes = table.get(offset..)?;
+    let len = memchr::memchr(0, bytes)?;
+
+    Some(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+/// The CRC32 used by `.gnu_debuglink`, which is the same as zlib's
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+/// Reads the fields of a 32-bit or 64-bit ELF file, where addresses and sizes are the size of a
+/// pointer
+struct Reader<'a> {
+    data: &'a [u8],
+    arch: ProcessArchitecture,
+}
+
+impl Reader<'_> {
+    fn read<T: Pod>(&self, offset: usize) -> Result<T> {
+        self.data
+            .get(offset..offset.saturating_add(std::mem::size_of::<T>()))
+            .map(bytemuck::pod_read_unaligned)
+            .ok_or(ElfError::OutOfBounds)
+    }
+
+    fn word(&self, offset: usize) -> Result<u64> {
+        match self.arch {
+            ProcessArchitecture::X86 => self.read::<u32>(offset).map(u64::from),
+            ProcessArchitecture::X64 => self.read::<u64>(offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A shared library linked at 0x10000 whose full symbol table is in a debug file, built from
+    /// symbols.c
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn dynamic_symbols() {
+        let image = ElfImage::open(fixture("libsymbols.so")).unwrap();
+        assert_eq!(image.arch, ProcessArchitecture::X64);
+        assert_eq!(image.load_address(), 0x10000);
+        assert!(image.section(".symtab").is_none());
+
+        let symbols = image.symbols().unwrap();
+        let names = symbols.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["shown_lines", "show_line"]);
+
+        let show_line = image.symbol("show_line").unwrap().unwrap();
+        assert_eq!(
+            (show_line.value, show_line.kind),
+            (0x1023C, SymbolKind::Function)
+        );
+        let shown_lines = image.symbol("shown_lines").unwrap().unwrap();
+        assert_eq!(
+            (shown_lines.value, shown_lines.size, shown_lines.kind),
+            (0x12000, 4, SymbolKind::Object)
+        );
+        assert_eq!(image.symbol("count_line").unwrap(), None);
+    }
+
+    #[test]
+    fn debug_file() {
+        let path = fixture("libsymbols.so");
+        let image = ElfImage::open(&path).unwrap();
+
+        let debug_file = std::fs::read(fixture("libsymbols.so.debug")).unwrap();
+        assert_eq!(
+            image.debug_link(),
+            Some(("libsymbols.so.debug".to_string(), crc32(&debug_file)))
+        );
+        assert_eq!(
+            find_debug_file(&path, &image),
+            Some(fixture("libsymbols.so.debug").canonicalize().unwrap())
+        );
+
+        // The local function is only in the symbol table of the debug file
+        let debug = ElfImage::parse(debug_file).unwrap();
+        let count_line = debug.symbol("count_line").unwrap().unwrap();
+        assert_eq!(count_line.value, 0x10230);
+
+        // Offsets are from the start of the first segment, which is mapped at the base
+        assert_eq!(symbol_offset(&path, "show_line").unwrap(), Some(0x23C));
+        assert_eq!(symbol_offset(&path, "count_line").unwrap(), Some(0x230));
+        assert_eq!(symbol_offset(&path, "missing").unwrap(), None);
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert!(matches!(
+            ElfImage::parse(b"MZ".to_vec()),
+            Err(ElfError::NotElf)
+        ));
+        assert!(matches!(
+            ElfImage::parse(b"\x7FELF\x02\x02".to_vec()),
+            Err(ElfError::BigEndian)
+        ));
+
+        let mut data = std::fs::read(fixture("libsymbols.so")).unwrap();
+        data.truncate(0x100);
+        assert!(ElfImage::parse(data).is_err_and(|err| matches!(err, ElfError::OutOfBounds)));
+    }
+}
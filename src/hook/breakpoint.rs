@@ -0,0 +1,211 @@
+use thiserror::Error;
+
+use super::code::{HookParam, Offset, TextType};
+use crate::{
+    memory::{self, MemorySource, ProcessArchitecture, RemoteAddress},
+    text::Decoded,
+};
+
+#[derive(Error, Debug)]
+pub enum EvaluateError {
+    #[error("The offset -{0:X} does not select a register")]
+    InvalidRegister(u64),
+    #[error("The code uses a code page that can not be decoded")]
+    UnknownCodepage,
+    #[error("Failed to read the text")]
+    Memory(#[from] memory::Error),
+}
+
+/// The general purpose registers of a thread. The registers of x86 threads are zero extended.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsp: u64,
+    pub rbp: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rip: u64,
+}
+
+impl Registers {
+    /// Returns the register a negative offset of a hook code selects.
+    ///
+    /// Textractor numbers the registers by where its trampoline pushes them, so on x86 `-4` is
+    /// EAX and `-20` is EDI, and on x64 `-C` is RAX and `-84` is R15.
+    pub fn by_offset(&self, arch: ProcessArchitecture, offset: i64) -> Option<u64> {
+        let offset = offset.unsigned_abs();
+
+        let register = match arch {
+            ProcessArchitecture::X86 => match offset {
+                0x04 => self.rax,
+                0x08 => self.rcx,
+                0x0C => self.rdx,
+                0x10 => self.rbx,
+                0x14 => self.rsp,
+                0x18 => self.rbp,
+                0x1C => self.rsi,
+                0x20 => self.rdi,
+                _ => return None,
+            },
+            ProcessArchitecture::X64 => match offset {
+                0x0C => self.rax,
+                0x14 => self.rbx,
+                0x1C => self.rcx,
+                0x24 => self.rdx,
+                0x2C => self.rsp,
+                0x34 => self.rbp,
+                0x3C => self.rsi,
+                0x44 => self.rdi,
+                0x4C => self.r8,
+                0x54 => self.r9,
+                0x5C => self.r10,
+                0x64 => self.r11,
+                0x6C => self.r12,
+                0x74 => self.r13,
+                0x7C => self.r14,
+                0x84 => self.r15,
+                _ => return None,
+            },
+        };
+
+        Some(truncate(arch, register))
+    }
+}
+
+/// The state of a thread at the moment it executed a hooked address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Context {
+    pub arch: ProcessArchitecture,
+    pub registers: Registers,
+}
+
+impl Context {
+    pub fn stack_pointer(&self) -> RemoteAddress {
+        RemoteAddress::new(truncate(self.arch, self.registers.rsp) as usize)
+    }
+}
+
+/// The text a hook found when it was hit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookText {
+    pub text: String,
+    /// The return address on top of the stack, unless the code has the `N` flag
+    pub context: Option<u64>,
+    /// The value selected by the split of the code, if it has one
+    pub split: Option<u64>,
+}
+
+/// Finds the text of an H-code in a thread that was stopped where it is hooked
+pub fn evaluate<S: MemorySource>(
+    hook: &HookParam,
+    source: &S,
+    context: &Context,
+) -> Result<HookText, EvaluateError> {
+    let encoding = hook.encoding().ok_or(EvaluateError::UnknownCodepage)?;
+    let value = resolve(hook.data, source, context)?;
+
+    let text = if hook.text_type.is_string() {
+        let address = RemoteAddress::new(value.wrapping_add(hook.padding.unwrap_or(0)) as usize);
+        let null_length = hook
+            .null_length
+            .map_or(encoding.unit_size(), |len| len as usize);
+
+        super::read_terminated(
+            source,
+            address,
+            encoding,
+            super::MAX_TEXT_LENGTH,
+            null_length,
+        )?
+    } else {
+        let bytes = match hook.text_type {
+            TextType::CharBigEndian => match (value as u16).to_be_bytes() {
+                [0, low] => vec![low],
+                bytes => bytes.to_vec(),
+            },
+            // A lead byte in the lowest byte is followed by the trail byte
+            TextType::Char => match (value as u16).to_le_bytes() {
+                [low, high] if encoding.decode_char(&[low]) == Decoded::Incomplete => {
+                    vec![low, high]
+                }
+                [low, _] => vec![low],
+            },
+            _ => (value as u16).to_le_bytes().to_vec(),
+        };
+
+        encoding.decode_lossy(&bytes)
+    };
+
+    let text = match hook.text_type {
+        TextType::WideCharHex | TextType::WideStringHex => text
+            .encode_utf16()
+            .map(|unit| format!("{unit:04X}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => text,
+    };
+
+    let return_address = match hook.no_context {
+        true => None,
+        false => Some(source.read_ptr(context.stack_pointer())?.get() as u64),
+    };
+
+    let split = hook
+        .split
+        .map(|split| resolve(split, source, context))
+        .transpose()?;
+
+    Ok(HookText {
+        text,
+        context: return_address,
+        split,
+    })
+}
+
+/// Returns the value an offset selects, which is a register if it is negative and a value on the
+/// stack otherwise
+pub fn resolve<S: MemorySource>(
+    offset: Offset,
+    source: &S,
+    context: &Context,
+) -> Result<u64, EvaluateError> {
+    let value = if offset.offset < 0 {
+        context
+            .registers
+            .by_offset(context.arch, offset.offset)
+            .ok_or(EvaluateError::InvalidRegister(offset.offset.unsigned_abs()))?
+    } else {
+        let address = context
+            .stack_pointer()
+            .checked_add(offset.offset as usize)
+            .ok_or(memory::Error::InvalidAddress(context.stack_pointer()))?;
+
+        source.read_ptr(address)?.get() as u64
+    };
+
+    match offset.deref {
+        Some(deref) => {
+            let address = RemoteAddress::new(value.wrapping_add_signed(deref) as usize);
+            Ok(source.read_ptr(address)?.get() as u64)
+        }
+        None => Ok(value),
+    }
+}
+
+fn truncate(arch: ProcessArchitecture, value: u64) -> u64 {
+    match arch {
+        ProcessArchitecture::X86 => value & 0xFFFF_FFFF,
+        ProcessArchitecture::X64 => value,
+    }
+}
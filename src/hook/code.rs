@@ -0,0 +1,510 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::text::Encoding;
+
+/// The code page Textractor assumes when a code does not specify one
+pub const DEFAULT_CODEPAGE: u32 = 932;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("The code is empty")]
+    Empty,
+    #[error("The code does not start with H or R")]
+    UnknownKind,
+    #[error("{0:?} is not a type of text hook codes can read")]
+    UnknownType(char),
+    #[error("{0:?} is not a valid number")]
+    InvalidNumber(String),
+    #[error("Expected '@' followed by an address, found {0:?}")]
+    MissingAddress(String),
+}
+
+/// Whether a code hooks an instruction or reads from a fixed address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CodeKind {
+    /// `H`, the text is taken from the registers or stack when the address is executed
+    #[default]
+    Hook,
+    /// `R`, the text is read from the address every so often
+    Read,
+}
+
+/// What a code reads, given by the letter after the kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TextType {
+    /// `A`, a double-byte character in the code page, stored big-endian
+    CharBigEndian,
+    /// `B`, a single- or double-byte character in the code page
+    Char,
+    /// `W`, a UTF-16 character
+    WideChar,
+    /// `H`, a UTF-16 character shown as hexadecimal
+    WideCharHex,
+    /// `S`, a pointer to a string in the code page
+    #[default]
+    String,
+    /// `Q`, a pointer to a UTF-16 string
+    WideString,
+    /// `V`, a pointer to a UTF-8 string
+    Utf8String,
+    /// `M`, a pointer to a UTF-16 string shown as hexadecimal
+    WideStringHex,
+}
+
+impl TextType {
+    fn from_letter(letter: char) -> Option<TextType> {
+        Some(match letter {
+            'A' => TextType::CharBigEndian,
+            'B' => TextType::Char,
+            'W' => TextType::WideChar,
+            'H' => TextType::WideCharHex,
+            'S' => TextType::String,
+            'Q' => TextType::WideString,
+            'V' => TextType::Utf8String,
+            'M' => TextType::WideStringHex,
+            _ => return None,
+        })
+    }
+
+    fn letter(self) -> char {
+        match self {
+            TextType::CharBigEndian => 'A',
+            TextType::Char => 'B',
+            TextType::WideChar => 'W',
+            TextType::WideCharHex => 'H',
+            TextType::String => 'S',
+            TextType::WideString => 'Q',
+            TextType::Utf8String => 'V',
+            TextType::WideStringHex => 'M',
+        }
+    }
+
+    /// Returns whether the hook receives a pointer to the text rather than a character
+    pub fn is_string(self) -> bool {
+        matches!(
+            self,
+            TextType::String
+                | TextType::WideString
+                | TextType::Utf8String
+                | TextType::WideStringHex
+        )
+    }
+}
+
+/// Where a hook finds a value, relative to the stack pointer when the hook is hit.
+///
+/// Negative offsets select a register instead, like in Textractor, e.g. `-4` is EAX and `-1C` is
+/// ESI on x86.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub offset: i64,
+    /// Adds this to the value and reads the pointer stored there, written as `*deref`
+    pub deref: Option<i64>,
+}
+
+/// A Textractor hook code, such as `/HS-1C@4A2B0:game.exe` or `/RS@44A8C0`.
+///
+/// The format of an H-code is
+/// `/H{type}[F][N][null_length<][codepage#][padding+]data[*deref][:split[*deref]]@addr[:module[:function]]`,
+/// and the format of an R-code is `/R{type}[null_length<][codepage#]@addr`. Everything that is
+/// specific to H-codes is left at its default for R-codes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct HookParam {
+    pub kind: CodeKind,
+    pub text_type: TextType,
+    /// `F`, keep reading until the whole string has been seen instead of stopping at a NUL
+    pub full_string: bool,
+    /// `N`, do not use the return address to tell threads calling the hook apart
+    pub no_context: bool,
+    /// The number of bytes the text is terminated by
+    pub null_length: Option<u32>,
+    pub codepage: Option<u32>,
+    /// The number of bytes to skip at the start of the text
+    pub padding: Option<u64>,
+    pub data: Offset,
+    /// The value used to tell apart different texts passing through the same hook
+    pub split: Option<Offset>,
+    /// The address, or the offset from the module or function if there is one
+    pub address: u64,
+    pub module: Option<String>,
+    pub function: Option<String>,
+}
+
+impl HookParam {
+    /// Returns the encoding of the text, or `None` if it uses a code page we can not decode
+    pub fn encoding(&self) -> Option<Encoding> {
+        match self.text_type {
+            TextType::WideChar
+            | TextType::WideCharHex
+            | TextType::WideString
+            | TextType::WideStringHex => Some(Encoding::Utf16Le),
+            TextType::Utf8String => Some(Encoding::Utf8),
+            TextType::CharBigEndian | TextType::Char | TextType::String => {
+                match self.codepage.unwrap_or(DEFAULT_CODEPAGE) {
+                    932 => Some(Encoding::ShiftJis),
+                    936 => Some(Encoding::Gbk),
+                    950 => Some(Encoding::Big5),
+                    20932 | 51932 => Some(Encoding::EucJp),
+                    65001 => Some(Encoding::Utf8),
+                    20127 => Some(Encoding::Ascii),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for HookParam {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim();
+        let code = code.strip_prefix('/').unwrap_or(code);
+        // Like Textractor, ignore everything after a second code for compatibility with AGTH
+        let code = code.split('/').next().unwrap_or_default().trim();
+
+        let mut chars = code.chars();
+        let kind = match chars.next().ok_or(ParseError::Empty)? {
+            'H' => CodeKind::Hook,
+            'R' => CodeKind::Read,
+            _ => return Err(ParseError::UnknownKind),
+        };
+
+        let letter = chars.next().ok_or(ParseError::Empty)?;
+        let text_type = TextType::from_letter(letter)
+            .filter(|text_type| kind == CodeKind::Hook || text_type.is_string())
+            .ok_or(ParseError::UnknownType(letter))?;
+
+        let mut param = HookParam {
+            kind,
+            text_type,
+            ..Default::default()
+        };
+        let mut rest = chars.as_str();
+
+        if kind == CodeKind::Hook {
+            param.full_string = eat(&mut rest, "F");
+            param.no_context = eat(&mut rest, "N");
+        }
+
+        param.null_length = parse_suffixed(&mut rest, '<', 10)?
+            .map(to_u32)
+            .transpose()?;
+        param.codepage = parse_suffixed(&mut rest, '#', 10)?
+            .map(to_u32)
+            .transpose()?;
+
+        if kind == CodeKind::Hook {
+            param.padding = parse_suffixed(&mut rest, '+', 16)?;
+            param.data = parse_offset(&mut rest)?.unwrap_or_default();
+
+            if eat(&mut rest, ":") {
+                param.split = Some(
+                    parse_offset(&mut rest)?
+                        .ok_or_else(|| ParseError::InvalidNumber(rest.to_string()))?,
+                );
+            }
+        }
+
+        let location = rest
+            .strip_prefix('@')
+            .ok_or_else(|| ParseError::MissingAddress(rest.to_string()))?;
+
+        // The module name ends at the first colon, the function name may contain more of them
+        let (address, names) = match location.split_once(':') {
+            Some((address, names)) if kind == CodeKind::Hook => (address, Some(names)),
+            _ => (location, None),
+        };
+
+        param.address = parse_hex(address)?;
+
+        if let Some(names) = names {
+            let (module, function) = match names.split_once(':') {
+                Some((module, function)) => (module, Some(function)),
+                None => (names, None),
+            };
+
+            param.module = Some(module.to_string());
+            param.function = function.map(str::to_string);
+        }
+
+        Ok(param)
+    }
+}
+
+impl fmt::Display for HookParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            CodeKind::Hook => 'H',
+            CodeKind::Read => 'R',
+        };
+        write!(f, "/{kind}{}", self.text_type.letter())?;
+
+        if self.kind == CodeKind::Hook {
+            if self.full_string {
+                f.write_str("F")?;
+            }
+            if self.no_context {
+                f.write_str("N")?;
+            }
+        }
+
+        if let Some(null_length) = self.null_length {
+            write!(f, "{null_length}<")?;
+        }
+        if let Some(codepage) = self.codepage {
+            write!(f, "{codepage}#")?;
+        }
+
+        if self.kind == CodeKind::Hook {
+            if let Some(padding) = self.padding {
+                write!(f, "{padding:X}+")?;
+            }
+
+            write!(f, "{}", self.data)?;
+            if let Some(split) = &self.split {
+                write!(f, ":{split}")?;
+            }
+        }
+
+        write!(f, "@{:X}", self.address)?;
+
+        if self.kind == CodeKind::Hook {
+            if let Some(module) = &self.module {
+                write!(f, ":{module}")?;
+
+                if let Some(function) = &self.function {
+                    write!(f, ":{function}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", SignedHex(self.offset))?;
+
+        if let Some(deref) = self.deref {
+            write!(f, "*{}", SignedHex(deref))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a number as `1C` or `-1C`, like Textractor does
+struct SignedHex(i64);
+
+impl fmt::Display for SignedHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(f, "{sign}{:X}", self.0.unsigned_abs())
+    }
+}
+
+/// Removes `prefix` from the start of `s`, returning whether it was there
+fn eat(s: &mut &str, prefix: &str) -> bool {
+    match s.strip_prefix(prefix) {
+        Some(rest) => {
+            *s = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Parses a number followed by `suffix`, such as the `932#` of a code page
+fn parse_suffixed(s: &mut &str, suffix: char, radix: u32) -> Result<Option<u64>, ParseError> {
+    let len = s.chars().take_while(|c| c.is_digit(radix)).count();
+
+    if len == 0 || !s[len..].starts_with(suffix) {
+        return Ok(None);
+    }
+
+    let number = u64::from_str_radix(&s[..len], radix)
+        .map_err(|_| ParseError::InvalidNumber(s[..len].to_string()))?;
+    *s = &s[len + suffix.len_utf8()..];
+
+    Ok(Some(number))
+}
+
+fn to_u32(number: u64) -> Result<u32, ParseError> {
+    u32::try_from(number).map_err(|_| ParseError::InvalidNumber(number.to_string()))
+}
+
+/// Parses an offset such as `-1C` or `-1C*4`
+fn parse_offset(s: &mut &str) -> Result<Option<Offset>, ParseError> {
+    let Some(offset) = parse_signed_hex(s)? else {
+        return Ok(None);
+    };
+
+    let deref = match eat(s, "*") {
+        true => Some(parse_signed_hex(s)?.ok_or_else(|| ParseError::InvalidNumber(s.to_string()))?),
+        false => None,
+    };
+
+    Ok(Some(Offset { offset, deref }))
+}
+
+fn parse_signed_hex(s: &mut &str) -> Result<Option<i64>, ParseError> {
+    let negative = s.starts_with('-');
+    let digits = &s[negative as usize..];
+    let len = digits.chars().take_while(char::is_ascii_hexdigit).count();
+
+    if len == 0 {
+        return Ok(None);
+    }
+
+    let magnitude = parse_hex(&digits[..len])?;
+    let value = i64::try_from(magnitude)
+        .map(|value| if negative { -value } else { value })
+        .map_err(|_| ParseError::InvalidNumber(digits[..len].to_string()))?;
+    *s = &digits[len..];
+
+    Ok(Some(value))
+}
+
+fn parse_hex(s: &str) -> Result<u64, ParseError> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ParseError::InvalidNumber(s.to_string()));
+    }
+
+    u64::from_str_radix(s, 16).map_err(|_| ParseError::InvalidNumber(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Codes as they are shared for real games, in the form Textractor writes them
+    const CORPUS: &[&str] = &[
+        "/HS-1C@4A2B0:game.exe",
+        "/HSN-4@1F3320:KiriKiri.exe",
+        "/HQ-8*0@CC1230:pal.dll",
+        "/HBN-4*0@4F37A0",
+        "/HW-4@0:gdi32.dll:GetGlyphOutlineW",
+        "/HA8@0:gdi32.dll:GetGlyphOutlineA",
+        "/HQN936#-C*C:C*4@4AA1D0:SiglusEngine.exe",
+        "/HS10<932#-1C@4A2B0:game.exe",
+        "/HSF4+-18@12AB0:vm.dll",
+        "/HVN-14*0@1FD9C0:libcocos2d.dll",
+        "/HM-8@5000",
+        "/HH-4:-8@4120F0",
+        "/HQ14@3F2DE0:Unity.exe:il2cpp::String::Concat",
+        "/RS@44A8C0",
+        "/RS932#@44A8C0",
+        "/RQ@7FF61234ABCD",
+        "/RV0<@1000",
+        "/RM2<@ABC",
+    ];
+
+    #[test]
+    fn corpus_round_trips() {
+        for code in CORPUS {
+            let param = code.parse::<HookParam>().unwrap();
+
+            assert_eq!(param.to_string(), *code);
+            assert_eq!(param.to_string().parse::<HookParam>().unwrap(), param);
+        }
+    }
+
+    #[test]
+    fn parses_fields() {
+        let param = "/HQN936#-C*C:C*4@4AA1D0:SiglusEngine.exe"
+            .parse::<HookParam>()
+            .unwrap();
+
+        assert_eq!(
+            param,
+            HookParam {
+                kind: CodeKind::Hook,
+                text_type: TextType::WideString,
+                no_context: true,
+                codepage: Some(936),
+                data: Offset {
+                    offset: -0xC,
+                    deref: Some(0xC),
+                },
+                split: Some(Offset {
+                    offset: 0xC,
+                    deref: Some(0x4),
+                }),
+                address: 0x4AA1D0,
+                module: Some("SiglusEngine.exe".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(param.encoding(), Some(Encoding::Utf16Le));
+    }
+
+    #[test]
+    fn function_names_may_contain_colons() {
+        let param = "/HQ14@3F2DE0:Unity.exe:il2cpp::String::Concat"
+            .parse::<HookParam>()
+            .unwrap();
+
+        assert_eq!(param.module.as_deref(), Some("Unity.exe"));
+        assert_eq!(param.function.as_deref(), Some("il2cpp::String::Concat"));
+    }
+
+    #[test]
+    fn normalizes_equivalent_codes() {
+        let cases = [
+            ("HS-1c@4a2b0:game.exe", "/HS-1C@4A2B0:game.exe"),
+            ("  /HS-1C@4A2B0  ", "/HS-1C@4A2B0"),
+            ("/HS@4A2B0", "/HS0@4A2B0"),
+            ("/HS-1C@4A2B0/HQ8@1000", "/HS-1C@4A2B0"),
+        ];
+
+        for (code, normalized) in cases {
+            assert_eq!(code.parse::<HookParam>().unwrap().to_string(), normalized);
+        }
+    }
+
+    #[test]
+    fn encoding_follows_code_page() {
+        let encoding = |code: &str| code.parse::<HookParam>().unwrap().encoding();
+
+        assert_eq!(encoding("/HS-1C@4A2B0"), Some(Encoding::ShiftJis));
+        assert_eq!(encoding("/HS936#-1C@4A2B0"), Some(Encoding::Gbk));
+        assert_eq!(encoding("/HS950#-1C@4A2B0"), Some(Encoding::Big5));
+        assert_eq!(encoding("/RS65001#@4A2B0"), Some(Encoding::Utf8));
+        assert_eq!(encoding("/RV@4A2B0"), Some(Encoding::Utf8));
+        assert_eq!(encoding("/HS1252#-1C@4A2B0"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert_eq!("".parse::<HookParam>(), Err(ParseError::Empty));
+        assert_eq!(
+            "/XS@1000".parse::<HookParam>(),
+            Err(ParseError::UnknownKind)
+        );
+        assert_eq!(
+            "/HZ@1000".parse::<HookParam>(),
+            Err(ParseError::UnknownType('Z'))
+        );
+        // Read codes can only read strings
+        assert_eq!(
+            "/RB@1000".parse::<HookParam>(),
+            Err(ParseError::UnknownType('B'))
+        );
+        assert_eq!(
+            "/HS-1C".parse::<HookParam>(),
+            Err(ParseError::MissingAddress(String::new()))
+        );
+        assert_eq!(
+            "/HS-1C@xyz".parse::<HookParam>(),
+            Err(ParseError::InvalidNumber("xyz".to_string()))
+        );
+        assert_eq!(
+            "/HS-1C:@1000".parse::<HookParam>(),
+            Err(ParseError::InvalidNumber("@1000".to_string()))
+        );
+    }
+}
@@ -0,0 +1,45 @@
+#![allow(dead_code)]
+
+use crate::{
+    memory::{self, MemorySource, RemoteAddress},
+    text::Encoding,
+};
+
+pub mod breakpoint;
+pub mod code;
+pub mod poll;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod ptrace;
+pub mod search;
+
+/// The most bytes of text a hook reads at once
+pub const MAX_TEXT_LENGTH: usize = 0x1000;
+
+/// Reads text that ends at the first run of `null_length` zero bytes that is aligned to the code
+/// units of the encoding. If `null_length` is 0, all `max_length` bytes are decoded.
+fn read_terminated<S: MemorySource>(
+    source: &S,
+    address: RemoteAddress,
+    encoding: Encoding,
+    max_length: usize,
+    null_length: usize,
+) -> memory::Result<String> {
+    let mut buf = vec![0; max_length];
+    let read = source.read(address, &mut buf)?;
+    let buf = &buf[..read];
+
+    let end = match null_length {
+        0 => buf.len(),
+        len => {
+            let unit = encoding.unit_size();
+            let terminator = vec![0; len];
+
+            (0..buf.len())
+                .step_by(unit)
+                .find(|&i| buf[i..].starts_with(&terminator))
+                .unwrap_or(buf.len() - buf.len() % unit)
+        }
+    };
+
+    Ok(encoding.decode_lossy(&buf[..end]))
+}
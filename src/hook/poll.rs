@@ -0,0 +1,347 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use thiserror::Error;
+
+use super::code::{CodeKind, HookParam};
+use crate::{
+    memory::{
+        self,
+        pointer::{PointerBase, PointerPath, ResolveError},
+        MemorySource, RemoteAddress,
+    },
+    text::Encoding,
+};
+
+#[derive(Error, Debug)]
+pub enum PollError {
+    #[error("Failed to follow the pointer path")]
+    Resolve(#[from] ResolveError),
+    #[error("Failed to read the text")]
+    Read(#[from] memory::Error),
+}
+
+#[derive(Debug)]
+pub enum PollEvent {
+    /// The text changed to a new line
+    Line(String),
+    /// The text could not be read, which is reported once until it can be read again
+    Unreadable(PollError),
+}
+
+/// Extracts text by reading it from the same place over and over, like a Textractor R-code.
+///
+/// This needs nothing but read access to the target, so it works with any [MemorySource]. The
+/// path is followed again on every poll, so it keeps working when the game moves its buffer.
+pub struct PollingHook {
+    pub path: PointerPath,
+    pub encoding: Encoding,
+    /// The most bytes the text may take up
+    pub max_length: usize,
+    /// The number of zero bytes the text ends with, or 0 if it always takes up `max_length`
+    pub null_length: usize,
+    pub interval: Duration,
+    /// Waits for the text to stay the same for one poll before reporting it, so that text which
+    /// is written a character at a time is only reported once
+    pub settle: bool,
+    last: Option<String>,
+    pending: Option<String>,
+}
+
+impl PollingHook {
+    pub fn new(path: PointerPath, encoding: Encoding) -> PollingHook {
+        PollingHook {
+            path,
+            encoding,
+            max_length: super::MAX_TEXT_LENGTH,
+            null_length: encoding.unit_size(),
+            interval: Duration::from_millis(100),
+            settle: true,
+            last: None,
+            pending: None,
+        }
+    }
+
+    /// Creates a hook from an R-code, unless it is an H-code or uses an unknown code page
+    pub fn from_code(code: &HookParam) -> Option<PollingHook> {
+        if code.kind != CodeKind::Read {
+            return None;
+        }
+
+        let path = PointerPath {
+            base: PointerBase::Absolute(RemoteAddress::new(usize::try_from(code.address).ok()?)),
+            offsets: Vec::new(),
+        };
+
+        let mut hook = PollingHook::new(path, code.encoding()?);
+        if let Some(null_length) = code.null_length {
+            hook.null_length = null_length as usize;
+        }
+
+        Some(hook)
+    }
+
+    /// Reads the text once, returning it if it is a new line
+    pub fn poll<S: MemorySource>(&mut self, source: &S) -> Result<Option<String>, PollError> {
+        let text = self.read(source)?;
+
+        // A cleared buffer is not a line, but the same line may show up again after it
+        if text.is_empty() || self.last.as_ref() == Some(&text) {
+            self.last = Some(text);
+            self.pending = None;
+            return Ok(None);
+        }
+
+        if self.settle && self.pending.as_ref() != Some(&text) {
+            self.pending = Some(text);
+            return Ok(None);
+        }
+
+        self.last = Some(text.clone());
+        self.pending = None;
+
+        Ok(Some(text))
+    }
+
+    /// Reads the text as it is right now
+    pub fn read<S: MemorySource>(&self, source: &S) -> Result<String, PollError> {
+        let address = self.path.resolve(source)?;
+
+        Ok(super::read_terminated(
+            source,
+            address,
+            self.encoding,
+            self.max_length,
+            self.null_length,
+        )?)
+    }
+
+    /// Polls on a thread of its own until the returned handle is stopped or dropped
+    pub fn spawn<S: MemorySource + Send + 'static>(mut self, source: S) -> PollingThread {
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+
+            move || {
+                let mut failing = false;
+
+                while !stop.load(Ordering::Relaxed) {
+                    let event = match self.poll(&source) {
+                        Ok(line) => {
+                            failing = false;
+                            line.map(PollEvent::Line)
+                        }
+                        Err(_) if failing => None,
+                        Err(e) => {
+                            failing = true;
+                            Some(PollEvent::Unreadable(e))
+                        }
+                    };
+
+                    // Nobody is listening anymore
+                    if event.is_some_and(|event| sender.send(event).is_err()) {
+                        break;
+                    }
+
+                    thread::park_timeout(self.interval);
+                }
+            }
+        });
+
+        PollingThread {
+            receiver,
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+/// A [PollingHook] running on a thread of its own
+pub struct PollingThread {
+    receiver: Receiver<PollEvent>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PollingThread {
+    /// Returns the receiving end of the events the hook emits
+    pub fn events(&self) -> &Receiver<PollEvent> {
+        &self.receiver
+    }
+
+    /// Stops polling and waits for the thread to exit
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            handle.thread().unpark();
+
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for PollingThread {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{mock::MockProcess, Protection};
+
+    const BUFFER: usize = 0x10000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    fn process() -> MockProcess {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BUFFER), 0x1000, Protection::READ_WRITE);
+        process
+    }
+
+    fn hook(encoding: Encoding) -> PollingHook {
+        let path = PointerPath {
+            base: PointerBase::Absolute(addr(BUFFER)),
+            offsets: Vec::new(),
+        };
+        PollingHook::new(path, encoding)
+    }
+
+    /// Replaces the text in the buffer, clearing what was there before
+    fn set_text(process: &MockProcess, encoding: Encoding, text: &str) {
+        process.write(addr(BUFFER), &[0; 0x100]).unwrap();
+        process
+            .write(addr(BUFFER), &encoding.encode(text).unwrap())
+            .unwrap();
+    }
+
+    /// Changes the text to each of `texts` in turn, polling after each change
+    fn lines(hook: &mut PollingHook, process: &MockProcess, texts: &[&str]) -> Vec<Option<String>> {
+        texts
+            .iter()
+            .map(|text| {
+                set_text(process, hook.encoding, text);
+                hook.poll(process).unwrap()
+            })
+            .collect()
+    }
+
+    fn line(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn settles() {
+        let process = process();
+        let mut hook = hook(Encoding::Utf16Le);
+
+        // Text written a character at a time is reported once it stops changing
+        let texts = [
+            "こ",
+            "こん",
+            "こんに",
+            "こんにちは",
+            "こんにちは",
+            "こんにちは",
+        ];
+        assert_eq!(
+            lines(&mut hook, &process, &texts),
+            [None, None, None, None, line("こんにちは"), None]
+        );
+
+        hook.settle = false;
+        assert_eq!(
+            lines(&mut hook, &process, &["さ", "さよ", "さよなら", "さよなら"]),
+            [line("さ"), line("さよ"), line("さよなら"), None]
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let process = process();
+        let mut hook = hook(Encoding::ShiftJis);
+        hook.settle = false;
+
+        let texts = ["一行目", "一行目", "二行目", "一行目", "一行目"];
+        assert_eq!(
+            lines(&mut hook, &process, &texts),
+            [line("一行目"), None, line("二行目"), line("一行目"), None]
+        );
+
+        // Text that changes back to the last line before it settles is not reported
+        hook.settle = true;
+        assert_eq!(
+            lines(&mut hook, &process, &["三行目", "一行目", "一行目"]),
+            [None, None, None]
+        );
+    }
+
+    #[test]
+    fn cleared_buffer() {
+        let process = process();
+        let mut hook = hook(Encoding::Utf8);
+
+        let texts = ["Line", "Line", "", "", "Line", "Line", "Line"];
+        assert_eq!(
+            lines(&mut hook, &process, &texts),
+            [None, line("Line"), None, None, None, line("Line"), None]
+        );
+
+        // Text that is cleared before it settles is never reported
+        assert_eq!(
+            lines(&mut hook, &process, &["Gone", "", "Gone"]),
+            [None, None, None]
+        );
+    }
+
+    #[test]
+    fn unreadable() {
+        let process = process();
+        let mut hook = hook(Encoding::Utf8);
+        hook.path.base = PointerBase::Absolute(addr(BUFFER + 0x1000));
+
+        assert!(matches!(hook.poll(&process), Err(PollError::Read(_))));
+    }
+
+    #[test]
+    fn from_code() {
+        let hook = |code: &str| PollingHook::from_code(&code.parse().unwrap());
+
+        let mut read = hook("/RQ@10000").unwrap();
+        assert_eq!(read.path.base, PointerBase::Absolute(addr(BUFFER)));
+        assert!(read.path.offsets.is_empty());
+        assert_eq!((read.encoding, read.null_length), (Encoding::Utf16Le, 2));
+
+        let process = process();
+        read.settle = false;
+        assert_eq!(
+            lines(&mut read, &process, &["テキスト"]),
+            [line("テキスト")]
+        );
+
+        let read = hook("/RS4<936#@ABC").unwrap();
+        assert_eq!((read.encoding, read.null_length), (Encoding::Gbk, 4));
+        assert_eq!(read.path.base, PointerBase::Absolute(addr(0xABC)));
+
+        // Hook codes need a hook, and the code page has to be one we can decode
+        assert!(hook("/HS-1C@4A2B0").is_none());
+        assert!(hook("/RS1252#@1000").is_none());
+    }
+}
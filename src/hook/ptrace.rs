@@ -0,0 +1,1003 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    marker::PhantomData,
+    mem::offset_of,
+};
+
+use libc::{c_int, c_uint, pid_t};
+use thiserror::Error;
+
+use super::{
+    breakpoint::{self, Context, EvaluateError, HookText, Registers},
+    code::{CodeKind, HookParam},
+};
+use crate::{
+    memory::{self, MemorySource, ModuleInfo, Process, RemoteAddress},
+    pe::{export::ExportTarget, PeError},
+};
+
+const INT3: u8 = 0xCC;
+/// How many times an export may be forwarded to another module before giving up
+const MAX_FORWARDS: usize = 8;
+/// DR0 to DR3 hold the addresses of data breakpoints
+const WATCH_SLOTS: usize = 4;
+/// DR6 tells which data breakpoint was hit
+const DEBUG_STATUS: usize = 6;
+/// DR7 enables the data breakpoints and sets what they watch for
+const DEBUG_CONTROL: usize = 7;
+
+#[derive(Error, Debug)]
+pub enum DebugError {
+    #[error("A ptrace call failed")]
+    Os(#[from] io::Error),
+    #[error("Failed to access the memory of the process")]
+    Memory(#[from] memory::Error),
+    #[error("Only H-codes can be set as breakpoints")]
+    NotHookCode,
+    #[error("The module {0:?} is not loaded")]
+    UnknownModule(String),
+    #[error("The function {0:?} is not exported")]
+    UnknownFunction(String),
+    #[error("Failed to read the exports of a module")]
+    Exports(#[from] PeError),
+    #[error("The address {0} is already hooked")]
+    AlreadyHooked(RemoteAddress),
+    #[error("All {WATCH_SLOTS} debug registers are in use")]
+    NoFreeWatch,
+    #[error("A watch must cover 1, 2, 4 or 8 bytes and be aligned to its length")]
+    InvalidWatch,
+}
+
+pub type Result<T> = std::result::Result<T, DebugError>;
+
+#[derive(Debug)]
+pub enum DebugEvent {
+    /// A thread executed a hooked address
+    Hit {
+        address: RemoteAddress,
+        thread: u32,
+        text: std::result::Result<HookText, EvaluateError>,
+    },
+    /// A thread accessed a watched address. The registers are those after the access, so the
+    /// instruction pointer is the address of the instruction that follows the one that made it.
+    Watch {
+        slot: usize,
+        address: RemoteAddress,
+        thread: u32,
+        context: Context,
+    },
+    /// The process exited
+    Exited,
+}
+
+/// What a watch is triggered by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchKind {
+    #[default]
+    Write,
+    ReadWrite,
+}
+
+/// A data breakpoint, which is set in the debug registers of every thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watch {
+    pub address: RemoteAddress,
+    pub len: usize,
+    pub kind: WatchKind,
+}
+
+struct Breakpoint {
+    hook: HookParam,
+    /// The byte that the int3 instruction replaced
+    original: u8,
+}
+
+/// Runs H-codes by attaching to a process with ptrace and setting software breakpoints.
+///
+/// When a thread hits a breakpoint, the text is read while it is stopped, then the original
+/// instruction is put back for a single step. Other threads that run over the address during that
+/// step are missed.
+///
+/// ptrace only accepts requests from the thread that attached, so the debugger must stay on the
+/// thread that created it. Dropping it removes the breakpoints and detaches.
+pub struct Debugger {
+    process: Process,
+    threads: HashSet<pid_t>,
+    breakpoints: HashMap<RemoteAddress, Breakpoint>,
+    watches: [Option<Watch>; WATCH_SLOTS],
+    /// Statuses that were taken while waiting for a specific thread
+    queued: VecDeque<(pid_t, c_int)>,
+    exited: bool,
+    _thread: PhantomData<*const ()>,
+}
+
+impl Debugger {
+    /// Attaches to every thread of a process, without stopping it
+    pub fn attach(process_id: u32) -> Result<Debugger> {
+        let mut debugger = Debugger {
+            process: Process::open(process_id)?,
+            threads: HashSet::new(),
+            breakpoints: HashMap::new(),
+            watches: [None; WATCH_SLOTS],
+            queued: VecDeque::new(),
+            exited: false,
+            _thread: PhantomData,
+        };
+
+        // Threads that are created while we attach are only traced if their parent already was,
+        // so keep going until no new ones show up
+        loop {
+            let mut attached = false;
+
+            for entry in std::fs::read_dir(format!("/proc/{process_id}/task"))? {
+                let Some(tid) = entry?.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                    continue;
+                };
+
+                if debugger.threads.contains(&tid) {
+                    continue;
+                }
+
+                match ptrace(
+                    libc::PTRACE_SEIZE,
+                    tid,
+                    0,
+                    libc::PTRACE_O_TRACECLONE as usize,
+                ) {
+                    Ok(_) => {
+                        debugger.threads.insert(tid);
+                        attached = true;
+                    }
+                    // The thread exited in the meantime
+                    Err(err) if err.raw_os_error() == Some(libc::ESRCH) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            }
+
+            if !attached {
+                break;
+            }
+        }
+
+        Ok(debugger)
+    }
+
+    pub fn process(&self) -> &Process {
+        &self.process
+    }
+
+    /// Sets a breakpoint for an H-code and returns the address it was set at
+    pub fn add_hook(&mut self, hook: HookParam) -> Result<RemoteAddress> {
+        if hook.kind != CodeKind::Hook {
+            return Err(DebugError::NotHookCode);
+        }
+
+        // The address is an offset from the function or the module, if either is given
+        let base = match (&hook.module, &hook.function) {
+            (Some(module), Some(function)) => self.resolve_function(module, function)?,
+            (Some(module), None) => find_module(&self.process.modules()?, module)?.base,
+            _ => RemoteAddress::NULL,
+        };
+        let address = base + hook.address as usize;
+
+        if self.breakpoints.contains_key(&address) {
+            return Err(DebugError::AlreadyHooked(address));
+        }
+
+        let original = self.process.read_u8(address)?;
+        self.process.write_u8(address, INT3)?;
+        self.breakpoints
+            .insert(address, Breakpoint { hook, original });
+
+        Ok(address)
+    }
+
+    /// Returns the address of a function exported by a module, following forwarders to the
+    /// modules they name. The function can also be an ordinal, written as `#42`, or a symbol of
+    /// an ELF module.
+    fn resolve_function(&self, module: &str, function: &str) -> Result<RemoteAddress> {
+        let modules = self.process.modules()?;
+        let (mut module, mut function) = (module.to_string(), function.to_string());
+
+        for _ in 0..MAX_FORWARDS {
+            let info = find_module(&modules, &module)?;
+            let image = match info.read_image(&self.process) {
+                Ok(image) => image,
+                // Native libraries are ELF files, which have symbols instead of exports
+                Err(memory::Error::Pe(PeError::NotPe)) => {
+                    return self
+                        .process
+                        .find_symbol(&module, &function)?
+                        .ok_or(DebugError::UnknownFunction(function));
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let export = match function
+                .strip_prefix('#')
+                .and_then(|ordinal| ordinal.parse().ok())
+            {
+                Some(ordinal) => image.export_by_ordinal(ordinal)?,
+                None => image.export(&function)?,
+            }
+            .ok_or_else(|| DebugError::UnknownFunction(function.clone()))?;
+
+            match export.target {
+                ExportTarget::Rva(rva) => return Ok(info.base + rva as usize),
+                // Forwarders name the module without its extension
+                ExportTarget::Forwarder(forwarder) => {
+                    let (forwarded_module, forwarded_function) = forwarder
+                        .split_once('.')
+                        .ok_or_else(|| DebugError::UnknownFunction(forwarder.clone()))?;
+
+                    module = format!("{forwarded_module}.dll");
+                    function = forwarded_function.to_string();
+                }
+            }
+        }
+
+        Err(DebugError::UnknownFunction(function))
+    }
+
+    /// Removes the breakpoint at an address and returns its code
+    pub fn remove_hook(&mut self, address: RemoteAddress) -> Result<Option<HookParam>> {
+        let Some(breakpoint) = self.breakpoints.remove(&address) else {
+            return Ok(None);
+        };
+
+        self.process.write_u8(address, breakpoint.original)?;
+
+        Ok(Some(breakpoint.hook))
+    }
+
+    /// Returns the codes that are set, along with their addresses
+    pub fn hooks(&self) -> impl Iterator<Item = (RemoteAddress, &HookParam)> {
+        self.breakpoints
+            .iter()
+            .map(|(address, breakpoint)| (*address, &breakpoint.hook))
+    }
+
+    /// Sets a data breakpoint on every thread and returns the debug register it uses.
+    ///
+    /// Only up to 8 bytes can be watched, so to find out when a line is written to a text
+    /// buffer, watch the first character.
+    pub fn add_watch(&mut self, watch: Watch) -> Result<usize> {
+        if !matches!(watch.len, 1 | 2 | 4 | 8) || !watch.address.get().is_multiple_of(watch.len) {
+            return Err(DebugError::InvalidWatch);
+        }
+
+        let slot = self
+            .watches
+            .iter()
+            .position(Option::is_none)
+            .ok_or(DebugError::NoFreeWatch)?;
+
+        self.watches[slot] = Some(watch);
+        if let Err(err) = self.update_watches() {
+            self.watches[slot] = None;
+            return Err(err);
+        }
+
+        Ok(slot)
+    }
+
+    /// Clears the data breakpoint in a debug register and returns what it watched
+    pub fn remove_watch(&mut self, slot: usize) -> Result<Option<Watch>> {
+        let Some(watch) = self.watches.get_mut(slot).and_then(Option::take) else {
+            return Ok(None);
+        };
+
+        self.update_watches()?;
+
+        Ok(Some(watch))
+    }
+
+    /// Returns the data breakpoints that are set, along with their debug registers
+    pub fn watches(&self) -> impl Iterator<Item = (usize, &Watch)> {
+        self.watches
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, watch)| Some((slot, watch.as_ref()?)))
+    }
+
+    /// Blocks until a hook is hit or the process exits
+    pub fn wait(&mut self) -> Result<DebugEvent> {
+        loop {
+            if let Some(event) = self.next_event(0)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// Returns the next event if there is one, without blocking
+    pub fn try_wait(&mut self) -> Result<Option<DebugEvent>> {
+        self.next_event(libc::WNOHANG)
+    }
+
+    /// Removes the breakpoints and lets the process run on its own
+    pub fn detach(mut self) -> Result<()> {
+        self.detach_all()
+    }
+
+    fn next_event(&mut self, flags: c_int) -> Result<Option<DebugEvent>> {
+        if self.exited {
+            return Ok(Some(DebugEvent::Exited));
+        }
+
+        while let Some((tid, status)) = self.queued.pop_front() {
+            if let Some(event) = self.handle_status(tid, status)? {
+                return Ok(Some(event));
+            }
+        }
+
+        loop {
+            let Some((tid, status)) = self.wait_threads(flags)? else {
+                if self.threads.is_empty() {
+                    self.exited = true;
+                    return Ok(Some(DebugEvent::Exited));
+                }
+                return Ok(None);
+            };
+
+            if let Some(event) = self.handle_status(tid, status)? {
+                return Ok(Some(event));
+            }
+        }
+    }
+
+    /// Collects the status of one of the traced threads. Other children of this process are left
+    /// alone, so whoever started them can still wait for them.
+    fn wait_threads(&mut self, flags: c_int) -> Result<Option<(pid_t, c_int)>> {
+        loop {
+            for tid in self.threads.iter().copied().collect::<Vec<_>>() {
+                let mut status = 0;
+                match unsafe { libc::waitpid(tid, &mut status, libc::__WALL | libc::WNOHANG) } {
+                    -1 => match io::Error::last_os_error() {
+                        err if err.kind() == io::ErrorKind::Interrupted => {}
+                        // The thread is gone without a status left to collect
+                        err if err.raw_os_error() == Some(libc::ECHILD) => {
+                            self.threads.remove(&tid);
+                        }
+                        err => return Err(err.into()),
+                    },
+                    0 => {}
+                    tid => return Ok(Some((tid, status))),
+                }
+            }
+
+            if flags & libc::WNOHANG != 0 || self.threads.is_empty() {
+                return Ok(None);
+            }
+
+            // Block until any child changes state, without collecting it as it may not be ours
+            let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+            let options = libc::WEXITED | libc::WSTOPPED | libc::WNOWAIT | libc::__WALL;
+            if unsafe { libc::waitid(libc::P_ALL, 0, &mut info, options) } == -1 {
+                match io::Error::last_os_error() {
+                    err if err.kind() == io::ErrorKind::Interrupted => {}
+                    err => return Err(err.into()),
+                }
+            } else if !self.threads.contains(&unsafe { info.si_pid() }) {
+                // Another child is waiting to be collected, so this would return right away
+                // until it is
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+        }
+    }
+
+    fn handle_status(&mut self, tid: pid_t, status: c_int) -> Result<Option<DebugEvent>> {
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            self.threads.remove(&tid);
+
+            // The main thread is reported last
+            if tid as u32 == self.process.process_id() {
+                self.exited = true;
+                self.threads.clear();
+                return Ok(Some(DebugEvent::Exited));
+            }
+
+            return Ok(None);
+        }
+
+        if !libc::WIFSTOPPED(status) {
+            return Ok(None);
+        }
+
+        // A thread that was never attached is not adopted, it may belong to another debugger
+        if !self.threads.contains(&tid) {
+            return Ok(None);
+        }
+
+        let signal = libc::WSTOPSIG(status);
+        match status >> 16 {
+            libc::PTRACE_EVENT_CLONE => {
+                let mut new_tid: libc::c_ulong = 0;
+                ptrace(
+                    libc::PTRACE_GETEVENTMSG,
+                    tid,
+                    0,
+                    &mut new_tid as *mut _ as usize,
+                )?;
+                let new_tid = new_tid as pid_t;
+
+                // Debug registers are not inherited, so they are set before the thread starts
+                if self.threads.insert(new_tid) {
+                    let status = wait_for(new_tid)?;
+                    if libc::WIFSTOPPED(status) {
+                        self.arm(new_tid)?;
+                        resume(new_tid, 0)?;
+                    } else {
+                        self.queued.push_back((new_tid, status));
+                    }
+                }
+
+                resume(tid, 0)?;
+                Ok(None)
+            }
+            // A group-stop must be left in place until the process is continued by a signal
+            libc::PTRACE_EVENT_STOP
+                if matches!(
+                    signal,
+                    libc::SIGSTOP | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU
+                ) =>
+            {
+                ptrace(libc::PTRACE_LISTEN, tid, 0, 0)?;
+                Ok(None)
+            }
+            0 if signal == libc::SIGTRAP => match trap_code(tid)? {
+                libc::SI_KERNEL => self.hit(tid),
+                libc::TRAP_HWBKPT => self.watch_hit(tid),
+                _ => {
+                    resume(tid, signal)?;
+                    Ok(None)
+                }
+            },
+            0 => {
+                resume(tid, signal)?;
+                Ok(None)
+            }
+            _ => {
+                resume(tid, 0)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn hit(&mut self, tid: pid_t) -> Result<Option<DebugEvent>> {
+        let mut regs = get_regs(tid)?;
+        let address = RemoteAddress::new(regs.rip as usize - 1);
+
+        // Back up to the instruction that was replaced
+        regs.rip -= 1;
+        set_regs(tid, &regs)?;
+
+        let Some(breakpoint) = self.breakpoints.get(&address) else {
+            // The breakpoint was removed after the thread hit it, so just carry on
+            if self.process.read_u8(address)? != INT3 {
+                resume(tid, 0)?;
+            } else {
+                regs.rip += 1;
+                set_regs(tid, &regs)?;
+                resume(tid, libc::SIGTRAP)?;
+            }
+
+            return Ok(None);
+        };
+
+        let context = Context {
+            arch: self.process.arch()?,
+            registers: to_registers(&regs),
+        };
+        let text = breakpoint::evaluate(&breakpoint.hook, &self.process, &context);
+        let original = breakpoint.original;
+
+        self.process.write_u8(address, original)?;
+        let exited = self.step(tid)?;
+        if self.breakpoints.contains_key(&address) {
+            self.process.write_u8(address, INT3)?;
+        }
+
+        if exited {
+            self.threads.remove(&tid);
+        } else {
+            resume(tid, 0)?;
+        }
+
+        Ok(Some(DebugEvent::Hit {
+            address,
+            thread: tid as u32,
+            text,
+        }))
+    }
+
+    fn watch_hit(&mut self, tid: pid_t) -> Result<Option<DebugEvent>> {
+        let status = peek_debug_register(tid, DEBUG_STATUS)?;
+        poke_debug_register(tid, DEBUG_STATUS, 0)?;
+
+        let regs = get_regs(tid)?;
+        resume(tid, 0)?;
+
+        // The watch may have been removed in the meantime
+        let Some((slot, watch)) = (0..WATCH_SLOTS)
+            .filter(|slot| status & (1 << slot) != 0)
+            .find_map(|slot| Some((slot, self.watches[slot]?)))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(DebugEvent::Watch {
+            slot,
+            address: watch.address,
+            thread: tid as u32,
+            context: Context {
+                arch: self.process.arch()?,
+                registers: to_registers(&regs),
+            },
+        }))
+    }
+
+    /// Returns the value of DR7 that enables the watches
+    fn debug_control(&self) -> usize {
+        let mut control = 0;
+
+        for (slot, watch) in self.watches() {
+            let access = match watch.kind {
+                WatchKind::Write => 0b01,
+                WatchKind::ReadWrite => 0b11,
+            };
+            let len = match watch.len {
+                1 => 0b00,
+                2 => 0b01,
+                8 => 0b10,
+                _ => 0b11,
+            };
+
+            control |= 1 << (slot * 2);
+            control |= (access | len << 2) << (16 + slot * 4);
+        }
+
+        control
+    }
+
+    /// Sets the watches in the debug registers of a stopped thread
+    fn arm(&self, tid: pid_t) -> Result<()> {
+        for (slot, watch) in self.watches() {
+            poke_debug_register(tid, slot, watch.address.get())?;
+        }
+
+        poke_debug_register(tid, DEBUG_CONTROL, self.debug_control())?;
+
+        Ok(())
+    }
+
+    /// Sets the watches on every thread, which have to be stopped for a moment to do so
+    fn update_watches(&mut self) -> Result<()> {
+        let threads = self.threads.iter().copied().collect::<Vec<_>>();
+
+        for tid in threads {
+            // A thread with a queued stop has not been resumed since, so it can be armed as is
+            let queued = self.queued.iter().find(|(queued, _)| *queued == tid);
+            if let Some(&(_, status)) = queued {
+                if libc::WIFSTOPPED(status) {
+                    self.arm(tid)?;
+                }
+                continue;
+            }
+
+            match ptrace(libc::PTRACE_INTERRUPT, tid, 0, 0) {
+                Ok(_) => {}
+                Err(err) if err.raw_os_error() == Some(libc::ESRCH) => continue,
+                Err(err) => return Err(err.into()),
+            }
+
+            let status = wait_for(tid)?;
+            if !libc::WIFSTOPPED(status) {
+                self.queued.push_back((tid, status));
+                continue;
+            }
+
+            self.arm(tid)?;
+
+            // The thread may have stopped for something else before it got to the interrupt,
+            // which is then handled like any other stop. The interrupt is dealt with later.
+            if status >> 16 == libc::PTRACE_EVENT_STOP && libc::WSTOPSIG(status) == libc::SIGTRAP {
+                resume(tid, 0)?;
+            } else {
+                self.queued.push_back((tid, status));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes a single instruction, returning whether the thread exited while doing so
+    fn step(&mut self, tid: pid_t) -> Result<bool> {
+        // Signals that arrive during the step are delivered afterwards
+        let mut pending = 0;
+
+        loop {
+            ptrace(libc::PTRACE_SINGLESTEP, tid, 0, 0)?;
+
+            let status = wait_for(tid)?;
+            if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+                return Ok(true);
+            }
+
+            match libc::WSTOPSIG(status) {
+                libc::SIGTRAP if status >> 16 == 0 => break,
+                _ if status >> 16 != 0 => {}
+                signal => pending = signal,
+            }
+        }
+
+        if pending != 0 {
+            unsafe { libc::syscall(libc::SYS_tgkill, self.process.process_id(), tid, pending) };
+        }
+
+        Ok(false)
+    }
+
+    fn detach_all(&mut self) -> Result<()> {
+        for (address, breakpoint) in self.breakpoints.drain() {
+            let _ = self.process.write_u8(address, breakpoint.original);
+        }
+
+        if self.exited {
+            return Ok(());
+        }
+
+        self.watches = [None; WATCH_SLOTS];
+
+        let threads = std::mem::take(&mut self.threads);
+        for &tid in &threads {
+            let _ = ptrace(libc::PTRACE_INTERRUPT, tid, 0, 0);
+        }
+
+        let queued = std::mem::take(&mut self.queued);
+        for tid in threads {
+            // A thread that is already stopped would not stop again until it is resumed
+            let status = match queued.iter().find(|(queued, _)| *queued == tid) {
+                Some(&(_, status)) => status,
+                None => match wait_for(tid) {
+                    Ok(status) => status,
+                    Err(_) => continue,
+                },
+            };
+            if !libc::WIFSTOPPED(status) {
+                continue;
+            }
+
+            poke_debug_register(tid, DEBUG_CONTROL, 0)?;
+
+            // Traps of breakpoints and watches that were hit before they were removed are undone,
+            // and other signals are passed on
+            let signal = match libc::WSTOPSIG(status) {
+                _ if status >> 16 != 0 => 0,
+                libc::SIGTRAP => match trap_code(tid)? {
+                    libc::SI_KERNEL => {
+                        let mut regs = get_regs(tid)?;
+                        regs.rip -= 1;
+                        set_regs(tid, &regs)?;
+                        0
+                    }
+                    libc::TRAP_HWBKPT => 0,
+                    _ => libc::SIGTRAP,
+                },
+                signal => signal,
+            };
+
+            ptrace(libc::PTRACE_DETACH, tid, 0, signal as usize)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        let _ = self.detach_all();
+    }
+}
+
+/// Finds a loaded module by its file name, ignoring case like Windows does
+fn find_module<'a>(modules: &'a [ModuleInfo], name: &str) -> Result<&'a ModuleInfo> {
+    modules
+        .iter()
+        .find(|module| module.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| DebugError::UnknownModule(name.to_string()))
+}
+
+fn ptrace(request: c_uint, tid: pid_t, addr: usize, data: usize) -> io::Result<libc::c_long> {
+    let ret = unsafe { libc::ptrace(request, tid, addr, data) };
+
+    match ret {
+        -1 => Err(io::Error::last_os_error()),
+        ret => Ok(ret),
+    }
+}
+
+fn resume(tid: pid_t, signal: c_int) -> io::Result<()> {
+    ptrace(libc::PTRACE_CONT, tid, 0, signal as usize).map(|_| ())
+}
+
+/// Waits for a specific thread to stop or exit and returns its status
+fn wait_for(tid: pid_t) -> io::Result<c_int> {
+    loop {
+        let mut status = 0;
+        match unsafe { libc::waitpid(tid, &mut status, libc::__WALL) } {
+            -1 => match io::Error::last_os_error() {
+                err if err.kind() == io::ErrorKind::Interrupted => continue,
+                err => return Err(err),
+            },
+            _ => return Ok(status),
+        }
+    }
+}
+
+/// Returns what raised a SIGTRAP, which is `SI_KERNEL` for an int3 instruction and
+/// `TRAP_HWBKPT` for a debug register
+fn trap_code(tid: pid_t) -> io::Result<c_int> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    ptrace(
+        libc::PTRACE_GETSIGINFO,
+        tid,
+        0,
+        &mut info as *mut _ as usize,
+    )?;
+
+    Ok(info.si_code)
+}
+
+fn debug_register_offset(index: usize) -> usize {
+    offset_of!(libc::user, u_debugreg) + index * std::mem::size_of::<libc::c_ulonglong>()
+}
+
+fn peek_debug_register(tid: pid_t, index: usize) -> io::Result<usize> {
+    ptrace(libc::PTRACE_PEEKUSER, tid, debug_register_offset(index), 0).map(|value| value as usize)
+}
+
+fn poke_debug_register(tid: pid_t, index: usize, value: usize) -> io::Result<()> {
+    ptrace(
+        libc::PTRACE_POKEUSER,
+        tid,
+        debug_register_offset(index),
+        value,
+    )
+    .map(|_| ())
+}
+
+fn get_regs(tid: pid_t) -> io::Result<libc::user_regs_struct> {
+    let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+    ptrace(libc::PTRACE_GETREGS, tid, 0, &mut regs as *mut _ as usize)?;
+
+    Ok(regs)
+}
+
+fn set_regs(tid: pid_t, regs: &libc::user_regs_struct) -> io::Result<()> {
+    ptrace(libc::PTRACE_SETREGS, tid, 0, regs as *const _ as usize).map(|_| ())
+}
+
+fn to_registers(regs: &libc::user_regs_struct) -> Registers {
+    Registers {
+        rax: regs.rax,
+        rbx: regs.rbx,
+        rcx: regs.rcx,
+        rdx: regs.rdx,
+        rsp: regs.rsp,
+        rbp: regs.rbp,
+        rsi: regs.rsi,
+        rdi: regs.rdi,
+        r8: regs.r8,
+        r9: regs.r9,
+        r10: regs.r10,
+        r11: regs.r11,
+        r12: regs.r12,
+        r13: regs.r13,
+        r14: regs.r14,
+        r15: regs.r15,
+        rip: regs.rip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    const LINE: &[u8] = b"Hello from the child\0";
+    /// Counts the lines the child has shown
+    static SHOWN: AtomicU64 = AtomicU64::new(0);
+
+    /// Stands in for the function of a game that draws a line of text
+    #[inline(never)]
+    extern "C" fn show_line(text: *const u8) {
+        std::hint::black_box(text);
+    }
+
+    /// Forks a child that keeps showing a line, at the same addresses as in this process
+    fn spawn_child() -> pid_t {
+        let pid = unsafe { libc::fork() };
+        assert!(pid >= 0, "fork failed");
+
+        if pid == 0 {
+            // Only async-signal-safe calls are made in the child, since the test runner is
+            // multithreaded
+            loop {
+                let show_line = std::hint::black_box(show_line as extern "C" fn(*const u8));
+                show_line(LINE.as_ptr());
+                SHOWN.fetch_add(1, Ordering::Relaxed);
+                unsafe { libc::usleep(1000) };
+            }
+        }
+
+        pid
+    }
+
+    fn kill(pid: pid_t) {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+            libc::waitpid(pid, std::ptr::null_mut(), 0);
+        }
+    }
+
+    #[test]
+    fn hook_is_hit() {
+        let pid = spawn_child();
+        let mut debugger = Debugger::attach(pid as u32).unwrap();
+
+        // The text is the first argument, which is passed in RDI
+        let function = RemoteAddress::new(show_line as *const () as usize);
+        let hook = format!("/HS-44@{:X}", function.get())
+            .parse::<HookParam>()
+            .unwrap();
+        assert_eq!(debugger.add_hook(hook.clone()).unwrap(), function);
+        assert!(matches!(
+            debugger.add_hook(hook),
+            Err(DebugError::AlreadyHooked(address)) if address == function
+        ));
+
+        for _ in 0..3 {
+            match debugger.wait().unwrap() {
+                DebugEvent::Hit {
+                    address,
+                    thread,
+                    text,
+                } => {
+                    assert_eq!(address, function);
+                    assert_eq!(thread, pid as u32);
+
+                    let text = text.unwrap();
+                    assert_eq!(text.text, "Hello from the child");
+                    assert!(text.context.is_some_and(|context| context != 0));
+                }
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+
+        // The original byte is put back
+        let original = unsafe { *(show_line as *const u8) };
+        assert!(debugger.remove_hook(function).unwrap().is_some());
+        assert_eq!(debugger.process().read_u8(function).unwrap(), original);
+
+        debugger.detach().unwrap();
+        kill(pid);
+    }
+
+    #[test]
+    fn other_children_are_left_alone() {
+        let pid = spawn_child();
+        let mut debugger = Debugger::attach(pid as u32).unwrap();
+
+        let function = RemoteAddress::new(show_line as *const () as usize);
+        let hook = format!("/HS-44@{:X}", function.get())
+            .parse::<HookParam>()
+            .unwrap();
+        debugger.add_hook(hook).unwrap();
+
+        let other = unsafe { libc::fork() };
+        assert!(other >= 0, "fork failed");
+        if other == 0 {
+            unsafe { libc::_exit(7) };
+        }
+
+        // Let it exit before the debugger waits, without collecting its status
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let options = libc::WEXITED | libc::WNOWAIT;
+        assert_eq!(
+            unsafe { libc::waitid(libc::P_PID, other as libc::id_t, &mut info, options) },
+            0
+        );
+
+        for _ in 0..3 {
+            assert!(matches!(debugger.wait().unwrap(), DebugEvent::Hit { .. }));
+        }
+
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(other, &mut status, 0) }, other);
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), 7);
+
+        debugger.detach().unwrap();
+        kill(pid);
+    }
+
+    #[test]
+    fn watch_is_hit() {
+        let pid = spawn_child();
+        let mut debugger = Debugger::attach(pid as u32).unwrap();
+
+        let address = RemoteAddress::new(SHOWN.as_ptr() as usize);
+        let watch = Watch {
+            address,
+            len: 8,
+            kind: WatchKind::Write,
+        };
+        let slot = debugger.add_watch(watch).unwrap();
+
+        let mut last = None;
+        for _ in 0..3 {
+            match debugger.wait().unwrap() {
+                DebugEvent::Watch {
+                    slot: hit,
+                    address: watched,
+                    thread,
+                    context,
+                } => {
+                    assert_eq!((hit, watched, thread), (slot, address, pid as u32));
+                    assert_ne!(context.registers.rip, 0);
+
+                    // The event comes after the write, so the count went up since the last one
+                    let shown = debugger.process().read_u64(address).unwrap();
+                    assert!(last.is_none_or(|last| shown > last));
+                    last = Some(shown);
+                }
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+
+        assert_eq!(debugger.remove_watch(slot).unwrap(), Some(watch));
+        assert_eq!(debugger.remove_watch(slot).unwrap(), None);
+
+        debugger.detach().unwrap();
+        kill(pid);
+    }
+
+    #[test]
+    fn watch_limits() {
+        let pid = spawn_child();
+        let mut debugger = Debugger::attach(pid as u32).unwrap();
+
+        let watch = |address: usize, len: usize| Watch {
+            address: RemoteAddress::new(SHOWN.as_ptr() as usize + address),
+            len,
+            kind: WatchKind::ReadWrite,
+        };
+
+        assert!(matches!(
+            debugger.add_watch(watch(0, 3)),
+            Err(DebugError::InvalidWatch)
+        ));
+        assert!(matches!(
+            debugger.add_watch(watch(2, 4)),
+            Err(DebugError::InvalidWatch)
+        ));
+
+        for slot in 0..WATCH_SLOTS {
+            assert_eq!(debugger.add_watch(watch(slot * 2, 2)).unwrap(), slot);
+        }
+        assert!(matches!(
+            debugger.add_watch(watch(0, 1)),
+            Err(DebugError::NoFreeWatch)
+        ));
+
+        // A freed debug register is reused
+        debugger.remove_watch(1).unwrap();
+        assert_eq!(debugger.add_watch(watch(0, 1)).unwrap(), 1);
+        assert_eq!(debugger.watches().count(), WATCH_SLOTS);
+
+        debugger.detach().unwrap();
+        kill(pid);
+    }
+}
@@ -0,0 +1,483 @@
+use std::collections::HashMap;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use std::time::{Duration, Instant};
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use thiserror::Error;
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use super::ptrace::{DebugError, DebugEvent, Debugger, Watch, WatchKind};
+use super::{
+    breakpoint::{self, Context},
+    code::{HookParam, Offset, TextType},
+};
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use crate::{memory, scan::text_diff::TextDiffScan};
+use crate::{
+    memory::{MemorySource, ModuleInfo, ProcessArchitecture, RemoteAddress},
+    text::Encoding,
+};
+
+/// A hook code that was found by a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookCandidate {
+    pub code: HookParam,
+    /// The number of times the code found text containing the phrase
+    pub hits: usize,
+    /// The number of times the hooked instruction accessed the text
+    pub accesses: usize,
+}
+
+impl HookCandidate {
+    /// Returns how often the code found the phrase when the text was accessed, between 0 and 1
+    pub fn reliability(&self) -> f64 {
+        self.hits as f64 / self.accesses as f64
+    }
+}
+
+/// The instructions seen accessing the text in one encoding
+#[derive(Default)]
+struct Site {
+    accesses: usize,
+    /// How often each operand pointed to the phrase, in the order they were first seen
+    operands: Vec<(Offset, usize)>,
+}
+
+/// Proposes hook codes from the threads that were caught accessing a line of text on screen.
+///
+/// Every time an instruction reads or writes a copy of the text, [record](Self::record) looks for
+/// the registers and stack slots that point to a string containing the phrase, either directly
+/// or through another pointer. Each of them is a candidate for an H-code at the instruction, and
+/// the ones that find the phrase every time the instruction touches the text are ranked first.
+pub struct HookSearch {
+    pub phrase: String,
+    /// How far above the stack pointer arguments are looked for
+    pub stack_depth: usize,
+    sites: HashMap<(RemoteAddress, Encoding), Site>,
+    modules: Option<Vec<ModuleInfo>>,
+}
+
+impl HookSearch {
+    pub fn new(phrase: impl Into<String>) -> HookSearch {
+        HookSearch {
+            phrase: phrase.into(),
+            stack_depth: 0x40,
+            sites: HashMap::new(),
+            modules: None,
+        }
+    }
+
+    /// Records a thread that accessed text in an encoding, with the instruction pointer at the
+    /// address the hook should be set at
+    pub fn record<S: MemorySource>(&mut self, source: &S, encoding: Encoding, context: &Context) {
+        if string_type(encoding).is_none() {
+            return;
+        }
+
+        let instruction = RemoteAddress::new(context.registers.rip as usize);
+        let site = self.sites.entry((instruction, encoding)).or_default();
+        site.accesses += 1;
+
+        // Operands often hold the same pointer, so each string is only read once
+        let mut strings = HashMap::new();
+
+        for offset in operands(context.arch, self.stack_depth) {
+            let Ok(pointer) = breakpoint::resolve(offset, source, context) else {
+                continue;
+            };
+
+            let found = *strings.entry(pointer).or_insert_with(|| {
+                super::read_terminated(
+                    source,
+                    RemoteAddress::new(pointer as usize),
+                    encoding,
+                    super::MAX_TEXT_LENGTH,
+                    encoding.unit_size(),
+                )
+                .is_ok_and(|text| text.contains(&self.phrase))
+            });
+
+            if found {
+                match site.operands.iter_mut().find(|(seen, _)| *seen == offset) {
+                    Some((_, hits)) => *hits += 1,
+                    None => site.operands.push((offset, 1)),
+                }
+            }
+        }
+
+        if self.modules.is_none() {
+            self.modules = source.modules().ok();
+        }
+    }
+
+    /// Returns the codes that found the phrase, most reliable first
+    pub fn candidates(&self) -> Vec<HookCandidate> {
+        let mut sites = self.sites.iter().collect::<Vec<_>>();
+        sites.sort_by_key(|((instruction, _), _)| *instruction);
+
+        let mut candidates = Vec::new();
+        for (&(instruction, encoding), site) in sites {
+            let Some((text_type, codepage)) = string_type(encoding) else {
+                continue;
+            };
+
+            let module = self
+                .modules
+                .iter()
+                .flatten()
+                .find(|module| module.range().contains(&instruction));
+            let (address, module) = match module {
+                Some(module) => (instruction - module.base, Some(module.name.clone())),
+                None => (instruction.get(), None),
+            };
+
+            for &(data, hits) in &site.operands {
+                candidates.push(HookCandidate {
+                    code: HookParam {
+                        text_type,
+                        codepage,
+                        data,
+                        address: address as u64,
+                        module: module.clone(),
+                        ..Default::default()
+                    },
+                    hits,
+                    accesses: site.accesses,
+                });
+            }
+        }
+
+        // Stable, so that registers stay ahead of the stack for the same instruction
+        candidates.sort_by(|a, b| {
+            b.reliability()
+                .total_cmp(&a.reliability())
+                .then(b.hits.cmp(&a.hits))
+        });
+
+        candidates
+    }
+}
+
+/// Returns the type of string and code page an H-code reads text in an encoding with
+fn string_type(encoding: Encoding) -> Option<(TextType, Option<u32>)> {
+    match encoding {
+        Encoding::Utf16Le => Some((TextType::WideString, None)),
+        Encoding::Utf8 => Some((TextType::Utf8String, None)),
+        // ASCII is a part of the default code page
+        Encoding::Ascii | Encoding::ShiftJis => Some((TextType::String, None)),
+        Encoding::EucJp => Some((TextType::String, Some(20932))),
+        Encoding::Gbk => Some((TextType::String, Some(936))),
+        Encoding::Big5 => Some((TextType::String, Some(950))),
+        Encoding::Utf16Be => None,
+    }
+}
+
+/// Returns the registers and stack slots that may hold a pointer to text, with and without
+/// following them to a second pointer
+fn operands(arch: ProcessArchitecture, stack_depth: usize) -> impl Iterator<Item = Offset> {
+    let registers = match arch {
+        ProcessArchitecture::X86 => (0x04..=0x20).step_by(4),
+        ProcessArchitecture::X64 => (0x0C..=0x84).step_by(8),
+    }
+    .map(|offset: i64| -offset);
+    let stack = (0..=stack_depth as i64).step_by(arch.pointer_size());
+
+    registers
+        .chain(stack)
+        .flat_map(|offset| [None, Some(0)].map(|deref| Offset { offset, deref }))
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[derive(Error, Debug)]
+pub enum SearchError {
+    #[error("The phrase was not found in memory")]
+    NotFound,
+    #[error("Failed to search the memory of the process")]
+    Memory(#[from] memory::Error),
+    #[error("Failed to watch the text")]
+    Debug(#[from] DebugError),
+}
+
+/// Searches for hooks that find a phrase, like Textractor's "Search for hooks".
+///
+/// The copies of the phrase in memory are watched for `duration`, during which the game should
+/// be made to show the line again, or the next one if it is written to the same place. Only as
+/// many copies as there are free debug registers can be watched at once.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub fn search(
+    debugger: &mut Debugger,
+    phrase: &str,
+    duration: Duration,
+) -> Result<Vec<HookCandidate>, SearchError> {
+    let mut matches = TextDiffScan::default().find(debugger.process(), phrase)?;
+    // ASCII text matches in every encoding that extends it
+    matches.dedup_by_key(|found| found.address);
+    matches.retain(|found| string_type(found.encoding).is_some());
+
+    if matches.is_empty() {
+        return Err(SearchError::NotFound);
+    }
+
+    let mut watched = HashMap::new();
+    for found in matches {
+        let len = found.encoding.encode(phrase).map_or(1, |bytes| bytes.len());
+        let watch = Watch {
+            address: found.address,
+            len: watch_len(found.address, len),
+            kind: WatchKind::ReadWrite,
+        };
+
+        match debugger.add_watch(watch) {
+            Ok(slot) => {
+                watched.insert(slot, found.encoding);
+            }
+            Err(DebugError::NoFreeWatch) => break,
+            Err(err) => {
+                remove_watches(debugger, &watched);
+                return Err(err.into());
+            }
+        }
+    }
+
+    let mut search = HookSearch::new(phrase);
+    let deadline = Instant::now() + duration;
+
+    let result = loop {
+        if Instant::now() >= deadline {
+            break Ok(());
+        }
+
+        match debugger.try_wait() {
+            Ok(Some(DebugEvent::Watch { slot, context, .. })) => {
+                if let Some(&encoding) = watched.get(&slot) {
+                    search.record(debugger.process(), encoding, &context);
+                }
+            }
+            Ok(Some(DebugEvent::Exited)) => break Ok(()),
+            Ok(Some(_)) => {}
+            Ok(None) => std::thread::sleep(Duration::from_millis(1)),
+            Err(err) => break Err(err),
+        }
+    };
+
+    remove_watches(debugger, &watched);
+    result?;
+
+    Ok(search.candidates())
+}
+
+/// Returns the most bytes at the start of the text a single watch can cover
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn watch_len(address: RemoteAddress, len: usize) -> usize {
+    [8, 4, 2]
+        .into_iter()
+        .find(|&size| size <= len && address.get().is_multiple_of(size))
+        .unwrap_or(1)
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn remove_watches(debugger: &mut Debugger, watched: &HashMap<usize, Encoding>) {
+    for &slot in watched.keys() {
+        let _ = debugger.remove_watch(slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hook::breakpoint::Registers,
+        memory::{mock::MockProcess, Protection},
+    };
+
+    const GAME: usize = 0x40_0000;
+    const LINE: usize = 0x2_0000;
+    const STACK: usize = 0x3_0000;
+    /// Holds a pointer to the line
+    const HOLDER: usize = 0x3_1000;
+
+    const RDI: Offset = Offset {
+        offset: -0x44,
+        deref: None,
+    };
+    const RSI: Offset = Offset {
+        offset: -0x3C,
+        deref: None,
+    };
+    const STACK_8: Offset = Offset {
+        offset: 8,
+        deref: Some(0),
+    };
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    fn process() -> MockProcess {
+        let mut process = MockProcess::new();
+        process.set_arch(ProcessArchitecture::X64);
+        process.add_module("game.exe", addr(GAME), 0x1_0000);
+
+        let mut line = Encoding::Utf16Le.encode("「Hello world」").unwrap();
+        line.resize(0x100, 0);
+        process.map(addr(LINE), line, Protection::READ_WRITE);
+        process.map_zeroed(addr(STACK), 0x100, Protection::READ_WRITE);
+        process.map_zeroed(addr(HOLDER), 0x100, Protection::READ_WRITE);
+
+        process.write_ptr(addr(HOLDER), addr(LINE)).unwrap();
+        process.write_ptr(addr(STACK + 8), addr(HOLDER)).unwrap();
+        process
+    }
+
+    fn context(rip: usize, registers: Registers) -> Context {
+        Context {
+            arch: ProcessArchitecture::X64,
+            registers: Registers {
+                rip: rip as u64,
+                rsp: STACK as u64,
+                ..registers
+            },
+        }
+    }
+
+    fn pointing_to_line() -> Registers {
+        Registers {
+            rdi: LINE as u64,
+            // Points to the second word of the line
+            rsi: LINE as u64 + 0xE,
+            ..Default::default()
+        }
+    }
+
+    fn operands(candidates: &[HookCandidate]) -> Vec<(Offset, usize, usize)> {
+        candidates
+            .iter()
+            .map(|candidate| (candidate.code.data, candidate.hits, candidate.accesses))
+            .collect()
+    }
+
+    #[test]
+    fn hits_are_recorded() {
+        let process = process();
+        let mut search = HookSearch::new("Hello");
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(GAME + 0x1234, pointing_to_line()),
+        );
+
+        let candidates = search.candidates();
+        assert_eq!(operands(&candidates), [(RDI, 1, 1), (STACK_8, 1, 1)]);
+
+        let code = &candidates[0].code;
+        assert_eq!(code.text_type, TextType::WideString);
+        assert_eq!(
+            (code.address, code.module.as_deref()),
+            (0x1234, Some("game.exe"))
+        );
+        assert_eq!(code.to_string(), "/HQ-44@1234:game.exe");
+        assert_eq!(candidates[1].code.to_string(), "/HQ8*0@1234:game.exe");
+
+        // The phrase is not in the text in any other encoding
+        let mut search = HookSearch::new("Hello");
+        search.record(&process, Encoding::Utf8, &context(GAME, pointing_to_line()));
+        search.record(
+            &process,
+            Encoding::Utf16Be,
+            &context(GAME, pointing_to_line()),
+        );
+        assert_eq!(search.candidates(), []);
+    }
+
+    #[test]
+    fn reliable_codes_are_ranked_first() {
+        let process = process();
+        let mut search = HookSearch::new("world");
+
+        // An instruction outside of any module that always finds the line
+        for _ in 0..3 {
+            search.record(
+                &process,
+                Encoding::Utf16Le,
+                &context(0x1000, pointing_to_line()),
+            );
+        }
+        // Another that only finds it through RDI half of the time
+        let instruction = GAME + 0x10;
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(instruction, pointing_to_line()),
+        );
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(instruction, Registers::default()),
+        );
+        // And one that found it once
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(GAME + 0x20, pointing_to_line()),
+        );
+
+        let candidates = search.candidates();
+        let ranked = candidates
+            .iter()
+            .map(|candidate| (candidate.code.address, candidate.code.data, candidate.hits))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranked,
+            [
+                (0x1000, RSI, 3),
+                (0x1000, RDI, 3),
+                (0x1000, STACK_8, 3),
+                (0x10, STACK_8, 2),
+                (0x20, RSI, 1),
+                (0x20, RDI, 1),
+                (0x20, STACK_8, 1),
+                (0x10, RSI, 1),
+                (0x10, RDI, 1),
+            ]
+        );
+        assert_eq!(candidates[0].code.module, None);
+        assert_eq!(candidates[8].reliability(), 0.5);
+    }
+
+    #[test]
+    fn text_that_changed_is_left_out() {
+        let process = process();
+        let mut search = HookSearch::new("Hello");
+        let instruction = GAME + 0x10;
+
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(instruction, pointing_to_line()),
+        );
+
+        // The next line is written over it
+        let other = Encoding::Utf16Le.encode("Goodbye\0").unwrap();
+        process.write(addr(LINE), &other).unwrap();
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(instruction, pointing_to_line()),
+        );
+
+        // The pointers no longer lead to the phrase, so only the first access counts
+        assert_eq!(
+            operands(&search.candidates()),
+            [(RDI, 1, 2), (STACK_8, 1, 2)]
+        );
+
+        // Operands that never found the phrase are not candidates at all
+        let mut search = HookSearch::new("Hello");
+        search.record(
+            &process,
+            Encoding::Utf16Le,
+            &context(instruction, pointing_to_line()),
+        );
+        assert_eq!(search.candidates(), []);
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(windows)]
 use windows::Win32::{
     Foundation::HMODULE,
     System::{
@@ -7,13 +8,24 @@ use windows::Win32::{
     },
 };
 
+#[cfg(windows)]
 mod def;
+mod elf;
+mod hook;
+#[cfg(windows)]
 mod id;
 mod memory;
+mod pe;
+mod scan;
+#[cfg(windows)]
 mod string;
+mod text;
+#[cfg(windows)]
 mod util;
+#[cfg(windows)]
 mod window;
 
+#[cfg(windows)]
 fn main() {
     let h_instance = unsafe { GetModuleHandleW(None).unwrap() };
     let mut si = STARTUPINFOW {
@@ -34,3 +46,8 @@ fn main() {
         );
     }
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("The user interface is only available on Windows");
+}
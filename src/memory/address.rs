@@ -0,0 +1,116 @@
+use std::{
+    fmt,
+    ops::{Add, AddAssign, Sub},
+};
+
+/// An address in the address space of a [MemorySource](super::MemorySource).
+///
+/// Unlike a pointer, this may never be dereferenced in our own process. It is as wide as a pointer
+/// of the host, which is at least as wide as a pointer of any process the host is able to open.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RemoteAddress(usize);
+
+impl RemoteAddress {
+    pub const NULL: RemoteAddress = RemoteAddress(0);
+
+    pub const fn new(address: usize) -> RemoteAddress {
+        RemoteAddress(address)
+    }
+
+    /// Returns the address as an integer
+    pub const fn get(self) -> usize {
+        self.0
+    }
+
+    pub const fn is_null(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Adds an offset to the address, returning `None` on overflow
+    pub fn checked_add(self, offset: usize) -> Option<RemoteAddress> {
+        self.0.checked_add(offset).map(RemoteAddress)
+    }
+
+    /// Adds a signed offset to the address, returning `None` on overflow or underflow
+    pub fn checked_offset(self, offset: isize) -> Option<RemoteAddress> {
+        self.0.checked_add_signed(offset).map(RemoteAddress)
+    }
+
+    /// Returns the address as a pointer for passing to the OS, it must not be dereferenced
+    pub(crate) fn as_ptr(self) -> *const std::ffi::c_void {
+        self.0 as *const std::ffi::c_void
+    }
+}
+
+impl From<usize> for RemoteAddress {
+    fn from(address: usize) -> RemoteAddress {
+        RemoteAddress(address)
+    }
+}
+
+impl From<RemoteAddress> for usize {
+    fn from(address: RemoteAddress) -> usize {
+        address.0
+    }
+}
+
+impl<T> From<*const T> for RemoteAddress {
+    fn from(ptr: *const T) -> RemoteAddress {
+        RemoteAddress(ptr as usize)
+    }
+}
+
+impl Add<usize> for RemoteAddress {
+    type Output = RemoteAddress;
+
+    fn add(self, offset: usize) -> RemoteAddress {
+        RemoteAddress(self.0 + offset)
+    }
+}
+
+impl AddAssign<usize> for RemoteAddress {
+    fn add_assign(&mut self, offset: usize) {
+        self.0 += offset;
+    }
+}
+
+impl Sub<usize> for RemoteAddress {
+    type Output = RemoteAddress;
+
+    fn sub(self, offset: usize) -> RemoteAddress {
+        RemoteAddress(self.0 - offset)
+    }
+}
+
+/// Returns the distance in bytes between two addresses
+impl Sub<RemoteAddress> for RemoteAddress {
+    type Output = usize;
+
+    fn sub(self, other: RemoteAddress) -> usize {
+        self.0 - other.0
+    }
+}
+
+impl fmt::Debug for RemoteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RemoteAddress({:#x})", self.0)
+    }
+}
+
+impl fmt::Display for RemoteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+impl fmt::LowerHex for RemoteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for RemoteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
@@ -0,0 +1,535 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use super::{
+    Error, MemoryRegion, MemorySource, ModuleInfo, ProcessArchitecture, RemoteAddress, Result,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// Counters describing how well a [CachedReader] is doing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Pages that were served from the cache, having been cached before the read
+    pub hits: u64,
+    /// Pages that had to be read from the source
+    pub misses: u64,
+    /// Calls made to the source, which is less than `misses` when adjacent pages are batched
+    pub source_reads: u64,
+    /// Pages dropped because they were written to, or the cache was invalidated
+    pub invalidations: u64,
+}
+
+impl CacheStats {
+    /// Returns the fraction of pages served from the cache, between 0 and 1
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+struct CachedPage {
+    data: Box<[u8]>,
+    /// The number of bytes from the start of the page that could be read
+    valid: usize,
+    fetched: Instant,
+}
+
+/// Wraps a [MemorySource] and caches what is read from it one page at a time.
+///
+/// A page is reused until it is older than the time-to-live, until the cache moves on to the next
+/// generation with [invalidate](Self::invalidate), or until it is written to through the reader.
+/// Reads that need several pages which are not cached fetch adjacent pages with a single read from
+/// the source.
+///
+/// When the cache is full, the pages that were fetched the longest ago make room for new ones.
+pub struct CachedReader<S: MemorySource> {
+    source: S,
+    ttl: Option<Duration>,
+    capacity: usize,
+    generation: Cell<u64>,
+    pages: RefCell<HashMap<usize, CachedPage>>,
+    stats: Cell<CacheStats>,
+}
+
+impl<S: MemorySource> CachedReader<S> {
+    /// Creates a cache that keeps pages until they are invalidated
+    pub fn new(source: S) -> CachedReader<S> {
+        CachedReader {
+            source,
+            ttl: None,
+            capacity: DEFAULT_CAPACITY,
+            generation: Cell::new(0),
+            pages: RefCell::new(HashMap::new()),
+            stats: Cell::new(CacheStats::default()),
+        }
+    }
+
+    /// Sets how long a page may be reused, or `None` to keep pages until they are invalidated
+    pub fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Sets the maximum number of pages to keep around
+    pub fn set_capacity(&mut self, pages: usize) {
+        self.capacity = pages;
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.set(CacheStats::default());
+    }
+
+    /// Returns the current generation, which increases every time the cache is invalidated
+    pub fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    /// Marks every cached page as stale, e.g. after the target has advanced a frame
+    pub fn invalidate(&self) {
+        self.generation.set(self.generation.get() + 1);
+
+        let dropped = std::mem::take(&mut *self.pages.borrow_mut()).len();
+        self.update_stats(|stats| stats.invalidations += dropped as u64);
+    }
+
+    /// Drops the cached pages overlapping `address..address + len`
+    pub fn invalidate_range(&self, address: RemoteAddress, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let first = address.get() / PAGE_SIZE;
+        let last = (address.get().saturating_add(len - 1)) / PAGE_SIZE;
+
+        let mut pages = self.pages.borrow_mut();
+        let dropped = (first..=last)
+            .filter(|page| pages.remove(page).is_some())
+            .count();
+        self.update_stats(|stats| stats.invalidations += dropped as u64);
+    }
+
+    /// Reads several ranges at once, returning the result of each read in the same order.
+    ///
+    /// All pages missing from the cache are fetched before any request is served, so that
+    /// requests close to each other share reads from the source.
+    pub fn read_many(&self, requests: &mut [(RemoteAddress, &mut [u8])]) -> Vec<Result<usize>> {
+        let requested = requests
+            .iter()
+            .flat_map(|(address, buffer)| page_range(*address, buffer.len()))
+            .collect::<HashSet<_>>();
+
+        let mut missing = requested
+            .iter()
+            .copied()
+            .filter(|page| !self.is_cached(*page))
+            .collect::<Vec<_>>();
+        missing.sort_unstable();
+
+        // Pages that fail to load are simply left out, the requests touching them will report
+        // the error when reading through to the source.
+        for run in missing.chunk_by(|a, b| a + 1 == *b) {
+            self.fetch_run(run[0], run.len(), &requested);
+        }
+
+        let results = requests
+            .iter_mut()
+            .map(|(address, buffer)| self.read_cached(*address, buffer, &missing))
+            .collect();
+
+        // A batch larger than the cache keeps all of its pages until it has been served
+        let mut pages = self.pages.borrow_mut();
+        if pages.len() > self.capacity {
+            self.evict(&mut pages, &HashSet::new());
+        }
+
+        results
+    }
+
+    fn is_cached(&self, page: usize) -> bool {
+        self.pages
+            .borrow()
+            .get(&page)
+            .is_some_and(|cached| self.is_fresh(cached))
+    }
+
+    fn is_fresh(&self, page: &CachedPage) -> bool {
+        self.ttl.is_none_or(|ttl| page.fetched.elapsed() < ttl)
+    }
+
+    /// Loads `count` pages starting at `first` into the cache, using as few reads as possible
+    fn fetch_run(&self, first: usize, count: usize, requested: &HashSet<usize>) {
+        let mut data = vec![0; count * PAGE_SIZE];
+
+        self.update_stats(|stats| {
+            stats.misses += count as u64;
+            stats.source_reads += 1;
+        });
+        let read = self
+            .source
+            .read(RemoteAddress::new(first * PAGE_SIZE), &mut data)
+            .unwrap_or(0);
+
+        let complete = read / PAGE_SIZE;
+        for (i, chunk) in data.chunks_exact(PAGE_SIZE).take(complete).enumerate() {
+            self.store(first + i, chunk.into(), PAGE_SIZE, requested);
+        }
+
+        // The read stopped in the page after the complete ones, which is kept if part of it could
+        // be read. The pages after it may still be readable, so they are retried one at a time.
+        let partial = read % PAGE_SIZE;
+        if complete < count && partial > 0 {
+            let data = data[complete * PAGE_SIZE..][..PAGE_SIZE].into();
+            self.store(first + complete, data, partial, requested);
+        }
+
+        for page in first + complete + 1..first + count {
+            let mut data = vec![0; PAGE_SIZE];
+
+            self.update_stats(|stats| stats.source_reads += 1);
+            match self
+                .source
+                .read(RemoteAddress::new(page * PAGE_SIZE), &mut data)
+            {
+                Ok(valid) if valid > 0 => self.store(page, data.into(), valid, requested),
+                _ => continue,
+            }
+        }
+    }
+
+    /// Adds a page to the cache, making room by evicting pages that are not part of the current
+    /// request
+    fn store(&self, page: usize, data: Box<[u8]>, valid: usize, requested: &HashSet<usize>) {
+        let mut pages = self.pages.borrow_mut();
+
+        if pages.len() >= self.capacity {
+            self.evict(&mut pages, requested);
+        }
+
+        pages.insert(
+            page,
+            CachedPage {
+                data,
+                valid,
+                fetched: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops stale pages, then the oldest pages outside of `keep` until about a quarter of the
+    /// cache is free, so that evicting is not needed for every page that is stored
+    fn evict(&self, pages: &mut HashMap<usize, CachedPage>, keep: &HashSet<usize>) {
+        pages.retain(|_, cached| self.is_fresh(cached));
+
+        let target = (self.capacity - self.capacity / 4).saturating_sub(1);
+        let excess = pages.len().saturating_sub(target);
+        if excess == 0 {
+            return;
+        }
+
+        let mut candidates = pages
+            .iter()
+            .filter(|(page, _)| !keep.contains(page))
+            .map(|(page, cached)| (cached.fetched, *page))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        for (_, page) in candidates.into_iter().take(excess) {
+            pages.remove(&page);
+        }
+    }
+
+    /// Fills a buffer from the cache, reading through to the source if a page is not available.
+    ///
+    /// Only pages that are not in `fetched`, which were loaded for this read, count as hits.
+    fn read_cached(
+        &self,
+        address: RemoteAddress,
+        buffer: &mut [u8],
+        fetched: &[usize],
+    ) -> Result<usize> {
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let cursor = address + done;
+            let page = cursor.get() / PAGE_SIZE;
+            let offset = cursor.get() % PAGE_SIZE;
+
+            let pages = self.pages.borrow();
+            let cached = match pages.get(&page) {
+                Some(cached) if self.is_fresh(cached) && offset < cached.valid => cached,
+                // Let the source decide what went wrong
+                _ if done == 0 => {
+                    drop(pages);
+                    self.update_stats(|stats| stats.source_reads += 1);
+                    return self.source.read(address, buffer);
+                }
+                _ => break,
+            };
+
+            let count = (cached.valid - offset).min(buffer.len() - done);
+            buffer[done..done + count].copy_from_slice(&cached.data[offset..offset + count]);
+            done += count;
+
+            if fetched.binary_search(&page).is_err() {
+                self.update_stats(|stats| stats.hits += 1);
+            }
+
+            // The rest of the page could not be read
+            if cached.valid < PAGE_SIZE && done < buffer.len() {
+                break;
+            }
+        }
+
+        Ok(done)
+    }
+
+    fn update_stats(&self, f: impl FnOnce(&mut CacheStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+impl<S: MemorySource> MemorySource for CachedReader<S> {
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        let mut requests = [(address, buffer)];
+
+        self.read_many(&mut requests)
+            .pop()
+            .unwrap_or(Err(Error::InvalidAddress(address)))
+    }
+
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+        let result = self.source.write(address, buffer);
+
+        // Even a failed write may have changed part of the memory
+        self.invalidate_range(address, buffer.len());
+
+        result
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        self.source.regions()
+    }
+
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        self.source.modules()
+    }
+
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        self.source.arch()
+    }
+}
+
+/// Returns the indices of the pages overlapping `address..address + len`
+fn page_range(address: RemoteAddress, len: usize) -> std::ops::Range<usize> {
+    let first = address.get() / PAGE_SIZE;
+    let end = address.get().saturating_add(len).div_ceil(PAGE_SIZE);
+
+    first..end.max(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{mock::MockProcess, Protection};
+
+    /// Counts the reads that reach the process behind the cache
+    struct Counting {
+        process: MockProcess,
+        reads: Cell<usize>,
+    }
+
+    impl MemorySource for Counting {
+        fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.process.read(address, buffer)
+        }
+
+        fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+            self.process.write(address, buffer)
+        }
+
+        fn regions(&self) -> Result<Vec<MemoryRegion>> {
+            self.process.regions()
+        }
+    }
+
+    /// A cache over 8 pages, where every byte holds the index of its page
+    fn cache() -> CachedReader<Counting> {
+        let mut process = MockProcess::new();
+        for page in 0..8 {
+            process.map(
+                RemoteAddress::new(page * PAGE_SIZE),
+                vec![page as u8; PAGE_SIZE],
+                Protection::READ_WRITE,
+            );
+        }
+
+        CachedReader::new(Counting {
+            process,
+            reads: Cell::new(0),
+        })
+    }
+
+    fn page(index: usize) -> RemoteAddress {
+        RemoteAddress::new(index * PAGE_SIZE)
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        let cache = cache();
+        let mut buffer = vec![0; PAGE_SIZE * 2];
+
+        // Two pages that are not cached yet are fetched with one read, and neither is a hit
+        cache.read_exact(page(2) + 0x800, &mut buffer).unwrap();
+        assert_eq!(cache.source().reads.get(), 1);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                misses: 3,
+                source_reads: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(cache.stats().hit_ratio(), 0.0);
+
+        cache.read_exact(page(2) + 0x800, &mut buffer).unwrap();
+        assert_eq!(cache.source().reads.get(), 1);
+        assert_eq!(cache.stats().hits, 3);
+        assert_eq!(cache.stats().hit_ratio(), 0.5);
+        assert_eq!(buffer[0x7FF..0x801], [2, 3]);
+    }
+
+    #[test]
+    fn batched_reads() {
+        let cache = cache();
+        cache.read_u8(page(1)).unwrap();
+        cache.reset_stats();
+
+        let (mut a, mut b, mut c) = ([0; 4], [0; 4], [0; 4]);
+        let results = cache.read_many(&mut [
+            (page(0) + 8, &mut a[..]),
+            (page(1) + 8, &mut b[..]),
+            (page(0) + 16, &mut c[..]),
+        ]);
+
+        assert!(results.iter().all(|result| matches!(result, Ok(4))));
+        assert_eq!((a, b, c), ([0; 4], [1; 4], [0; 4]));
+        // Page 0 was fetched for this batch, so only the earlier page 1 is a hit
+        assert_eq!(cache.source().reads.get(), 2);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                source_reads: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn eviction_keeps_the_current_batch() {
+        let mut cache = cache();
+        cache.set_capacity(2);
+
+        // A batch larger than the cache is served entirely from what it fetched
+        let mut buffers = [[0; 4]; 4];
+        let mut requests = buffers
+            .iter_mut()
+            .enumerate()
+            .map(|(i, buffer)| (page(i * 2), &mut buffer[..]))
+            .collect::<Vec<_>>();
+        let results = cache.read_many(&mut requests);
+
+        assert!(results.iter().all(|result| matches!(result, Ok(4))));
+        assert_eq!(buffers, [[0; 4], [2; 4], [4; 4], [6; 4]]);
+        assert_eq!(cache.source().reads.get(), 4);
+        assert!(cache.pages.borrow().len() <= 2);
+
+        // The oldest page makes room for the newest one
+        cache.invalidate();
+        let reads = cache.source().reads.get();
+        cache.read_u8(page(0)).unwrap();
+        cache.read_u8(page(1)).unwrap();
+        cache.read_u8(page(2)).unwrap();
+        assert_eq!(cache.source().reads.get(), reads + 3);
+
+        cache.read_u8(page(2)).unwrap();
+        assert_eq!(cache.source().reads.get(), reads + 3);
+        cache.read_u8(page(0)).unwrap();
+        assert_eq!(cache.source().reads.get(), reads + 4);
+    }
+
+    #[test]
+    fn writes_and_failures() {
+        let cache = cache();
+        assert_eq!(cache.read_u8(page(3)).unwrap(), 3);
+
+        cache.write_u8(page(3), 0x33).unwrap();
+        assert_eq!(cache.stats().invalidations, 1);
+        assert_eq!(cache.read_u8(page(3)).unwrap(), 0x33);
+        assert_eq!(cache.source().reads.get(), 2);
+
+        // Unmapped memory is not cached, and the read through reports the error
+        assert!(matches!(
+            cache.read_u8(page(9)),
+            Err(Error::InvalidAddress(address)) if address == page(9)
+        ));
+        assert_eq!(cache.stats().source_reads, 4);
+        assert_eq!(cache.source().reads.get(), 4);
+
+        // A read that runs off the end of the mapping stops there
+        let mut buffer = [0; 0x20];
+        assert_eq!(cache.read(page(8) - 0x10, &mut buffer).unwrap(), 0x10);
+    }
+
+    #[test]
+    fn gaps_in_a_batch() {
+        let mut cache = cache();
+        cache.source.process.unmap(page(5));
+
+        let (mut a, mut b, mut c) = ([0; 4], [0; 4], [0; 4]);
+        let results = cache.read_many(&mut [
+            (page(4), &mut a[..]),
+            (page(5), &mut b[..]),
+            (page(6), &mut c[..]),
+        ]);
+
+        // The run over pages 4 to 6 stops at 5, so 6 is retried on its own and 5 is read through
+        // to report the error
+        assert!(matches!(
+            results[..],
+            [Ok(4), Err(Error::InvalidAddress(_)), Ok(4)]
+        ));
+        assert_eq!((a, c), ([4; 4], [6; 4]));
+        assert_eq!(cache.source().reads.get(), 3);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                misses: 3,
+                source_reads: 3,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use std::{
+    cell::OnceCell,
+    collections::HashSet,
+    fs::{File, OpenOptions, ReadDir},
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    find_export, Error, FileInfo, MemoryRegion, MemorySource, ModuleInfo, ProcessArchitecture,
+    Protection, RegionKind, RegionState, RemoteAddress, Result,
+};
+use crate::{
+    elf::{self, ElfError},
+    pe::{icon::IconImage, PeImage},
+};
+
+pub struct ProcessSnapshot {
+    entries: ReadDir,
+}
+
+impl ProcessSnapshot {
+    /// Creates an iterable snapshot of the currently running processes
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            entries: std::fs::read_dir("/proc")?,
+        })
+    }
+}
+
+impl Iterator for ProcessSnapshot {
+    type Item = ProcessEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.entries.next()?.ok()?;
+
+            let Some(process_id) = entry.file_name().to_str().and_then(|s| s.parse().ok()) else {
+                continue;
+            };
+
+            // The process may have exited since we listed the directory
+            if let Some(entry) = ProcessEntry::read(process_id) {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+pub struct ProcessEntry {
+    process_id: u32,
+    parent_process_id: u32,
+    name: String,
+    executable_path: Option<PathBuf>,
+}
+
+impl ProcessEntry {
+    fn read(process_id: u32) -> Option<ProcessEntry> {
+        // The name in stat is enclosed in parentheses and may itself contain parentheses or spaces,
+        // so the fields are parsed from after the last one. See proc_pid_stat(5).
+        let stat = std::fs::read_to_string(format!("/proc/{process_id}/stat")).ok()?;
+        let (stat_name, fields) = stat.split_once('(')?.1.rsplit_once(')')?;
+        let parent_process_id = fields.split_ascii_whitespace().nth(1)?.parse().ok()?;
+
+        let comm = std::fs::read_to_string(format!("/proc/{process_id}/comm"))
+            .map(|comm| comm.trim_end_matches('\n').to_string())
+            .unwrap_or_else(|_| stat_name.to_string());
+
+        // Reading the link requires the same permissions as opening the process
+        let executable_path = std::fs::read_link(format!("/proc/{process_id}/exe")).ok();
+
+        // comm is truncated to 15 bytes, so prefer the executable name when it matches. Under Wine
+        // the executable is the preloader while comm holds the name of the Windows executable.
+        let name = executable_path
+            .as_deref()
+            .and_then(Path::file_name)
+            .and_then(|name| name.to_str())
+            .filter(|name| name.starts_with(&comm))
+            .map(str::to_string)
+            .unwrap_or(comm);
+
+        Some(ProcessEntry {
+            process_id,
+            parent_process_id,
+            name,
+            executable_path,
+        })
+    }
+
+    /// Returns the process id of the process
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the process id of the parent process
+    pub fn parent_process_id(&self) -> u32 {
+        self.parent_process_id
+    }
+
+    /// Returns the name of the process
+    pub fn process_name(&self) -> Result<String> {
+        Ok(self.name.clone())
+    }
+
+    /// Returns the full path of the executable, if we are allowed to see it
+    pub fn executable_path(&self) -> Option<&Path> {
+        self.executable_path.as_deref()
+    }
+
+    pub fn modules(&self) -> Result<ModuleSnapshot> {
+        ModuleSnapshot::new(self.process_id())
+    }
+
+    pub fn open(&mut self) -> Result<Process> {
+        Process::open(self.process_id())
+    }
+}
+
+pub struct ModuleSnapshot {
+    modules: std::vec::IntoIter<Module>,
+}
+
+impl ModuleSnapshot {
+    /// Creates an iterable snapshot of the modules loaded by a process
+    ///
+    /// Every file mapped into the process is treated as a module, spanning from its lowest to its
+    /// highest mapped address.
+    pub fn new(process_id: u32) -> Result<Self> {
+        let mut modules: Vec<Module> = Vec::new();
+
+        for mapping in read_maps(process_id)? {
+            let Some(path) = mapping.path.filter(|path| path.is_absolute()) else {
+                continue;
+            };
+
+            match modules.iter_mut().find(|module| module.path == path) {
+                Some(module) => {
+                    let end = (module.base + module.size).max(mapping.end);
+                    module.base = module.base.min(mapping.start);
+                    module.size = end - module.base;
+                }
+                None => modules.push(Module {
+                    process_id,
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    path,
+                    base: mapping.start,
+                    size: mapping.end - mapping.start,
+                }),
+            }
+        }
+
+        Ok(Self {
+            modules: modules.into_iter(),
+        })
+    }
+}
+
+impl Iterator for ModuleSnapshot {
+    type Item = Module;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.modules.next()
+    }
+}
+
+pub struct Module {
+    process_id: u32,
+    name: String,
+    path: PathBuf,
+    base: usize,
+    size: usize,
+}
+
+impl Module {
+    /// Returns the id of the process the module is loaded in
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the file name of the module
+    pub fn module_name(&self) -> Result<String> {
+        Ok(self.name.clone())
+    }
+
+    pub fn module_addr(&self) -> RemoteAddress {
+        RemoteAddress::new(self.base)
+    }
+
+    /// Returns the number of bytes between the first and the last mapped byte of the module
+    pub fn module_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the full path of the module file
+    pub fn module_path(&self) -> Result<PathBuf> {
+        Ok(self.path.clone())
+    }
+
+    /// Whether an address lies within the memory of the module
+    pub fn contains(&self, address: RemoteAddress) -> bool {
+        (self.module_addr()..self.module_addr() + self.module_size()).contains(&address)
+    }
+}
+
+pub struct Process {
+    process_id: u32,
+    /// `/proc/<pid>/mem`, used whenever `process_vm_readv`/`process_vm_writev` are unavailable
+    mem: Option<File>,
+    arch: OnceCell<ProcessArchitecture>,
+}
+
+impl Process {
+    /// Opens the memory of the given process id
+    pub fn open(process_id: u32) -> Result<Process> {
+        // Fails with `NotFound` if the process does not exist
+        std::fs::metadata(format!("/proc/{process_id}"))?;
+
+        let path = format!("/proc/{process_id}/mem");
+        let mem = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .or_else(|_| File::open(&path))
+            .ok();
+
+        Ok(Process {
+            process_id,
+            mem,
+            arch: OnceCell::new(),
+        })
+    }
+
+    /// Returns the process id opened
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the module an address lies in, such as to write it as an offset from the module
+    pub fn module_for_address(&self, address: RemoteAddress) -> Result<Option<Module>> {
+        Ok(ModuleSnapshot::new(self.process_id)?.find(|module| module.contains(address)))
+    }
+
+    /// Reads the architecture of the process from the ELF header of its executable
+    fn read_arch(&self) -> Result<ProcessArchitecture> {
+        let mut ident = [0; 5];
+        File::open(format!("/proc/{}/exe", self.process_id))?.read_exact_at(&mut ident, 0)?;
+
+        // EI_CLASS is 1 for 32-bit objects and 2 for 64-bit objects
+        match ident {
+            [0x7f, b'E', b'L', b'F', 1] => Ok(ProcessArchitecture::X86),
+            [0x7f, b'E', b'L', b'F', 2] => Ok(ProcessArchitecture::X64),
+            _ => Err(Error::Io(std::io::ErrorKind::InvalidData.into())),
+        }
+    }
+
+    pub fn executable_path(&self) -> Result<PathBuf> {
+        Ok(std::fs::read_link(format!(
+            "/proc/{}/exe",
+            self.process_id
+        ))?)
+    }
+
+    /// Returns the version information of the Windows executable the process runs, in each of
+    /// its languages
+    pub fn file_descriptions(&self) -> Result<Vec<FileInfo>> {
+        for path in self.windows_executables()? {
+            match super::read_file_info(&path) {
+                Ok(infos) if !infos.is_empty() => return Ok(infos),
+                Ok(_) | Err(Error::Pe(_)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Returns the image of the icon of the Windows executable the process runs that is closest
+    /// to `size` pixels
+    pub fn icon(&self, size: u32) -> Result<Option<IconImage>> {
+        for path in self.windows_executables()? {
+            if let Ok(Some(icon)) = PeImage::open(path).and_then(|image| image.icon(size)) {
+                return Ok(Some(icon));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the paths that may be the Windows executable the process runs.
+    ///
+    /// Under Wine the executable of the process is the loader, so the `.exe` images mapped into
+    /// the process are listed after it.
+    fn windows_executables(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = vec![self.executable_path()?];
+        for path in read_maps(self.process_id)?
+            .into_iter()
+            .filter_map(|mapping| mapping.path)
+        {
+            let is_exe = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"));
+
+            if is_exe && !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    fn mem(&self) -> Result<&File> {
+        self.mem
+            .as_ref()
+            .ok_or_else(|| Error::Io(std::io::ErrorKind::PermissionDenied.into()))
+    }
+}
+
+impl MemorySource for Process {
+    /// Reads the memory of the open process at an address into a buffer
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let local = libc::iovec {
+            iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: address.get() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+
+        let ret = unsafe {
+            libc::process_vm_readv(self.process_id as libc::pid_t, &local, 1, &remote, 1, 0)
+        };
+
+        if ret >= 0 {
+            return Ok(ret as usize);
+        }
+
+        match std::io::Error::last_os_error() {
+            err if should_fall_back(&err) => {
+                let mem = self.mem()?;
+                transfer_partial(address, buffer.len(), |done| {
+                    mem.read_at(&mut buffer[done..], (address + done).get() as u64)
+                })
+            }
+            err => Err(map_error(address, err)),
+        }
+    }
+
+    /// Writes the contents of a buffer to a given address within the memory of the open process and return the number of bytes written
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let local = libc::iovec {
+            iov_base: buffer.as_ptr() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+        let remote = libc::iovec {
+            iov_base: address.get() as *mut libc::c_void,
+            iov_len: buffer.len(),
+        };
+
+        let ret = unsafe {
+            libc::process_vm_writev(self.process_id as libc::pid_t, &local, 1, &remote, 1, 0)
+        };
+
+        if ret >= 0 {
+            return Ok(ret as usize);
+        }
+
+        // process_vm_writev respects page protections, unlike /proc/<pid>/mem which can also
+        // write to read-only pages (e.g. code), so we fall back to it on EFAULT as well.
+        match std::io::Error::last_os_error() {
+            err if should_fall_back(&err)
+                || (err.raw_os_error() == Some(libc::EFAULT) && self.mem.is_some()) =>
+            {
+                let mem = self.mem()?;
+                transfer_partial(address, buffer.len(), |done| {
+                    mem.write_at(&buffer[done..], (address + done).get() as u64)
+                })
+            }
+            err => Err(map_error(address, err)),
+        }
+    }
+
+    /// Returns the mapped regions of the process. Unmapped gaps are not reported.
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        let maps = read_maps(self.process_id)?;
+
+        // Files that have executable mappings are considered images, other files are just mapped
+        let images = maps
+            .iter()
+            .filter(|mapping| mapping.perms[2] == b'x')
+            .filter_map(|mapping| mapping.path.as_ref())
+            .collect::<HashSet<_>>();
+
+        Ok(maps
+            .iter()
+            .map(|mapping| MemoryRegion {
+                base: RemoteAddress::new(mapping.start),
+                size: mapping.end - mapping.start,
+                protection: Protection {
+                    read: mapping.perms[0] == b'r',
+                    write: mapping.perms[1] == b'w',
+                    execute: mapping.perms[2] == b'x',
+                },
+                state: RegionState::Committed,
+                kind: match &mapping.path {
+                    Some(path) if path.is_absolute() && images.contains(path) => {
+                        RegionKind::Image(Some(path.clone()))
+                    }
+                    Some(path) if path.is_absolute() => RegionKind::Mapped(Some(path.clone())),
+                    _ => RegionKind::Private,
+                },
+            })
+            .collect())
+    }
+
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        Ok(ModuleSnapshot::new(self.process_id)?
+            .map(|module| ModuleInfo {
+                name: module.name,
+                base: RemoteAddress::new(module.base),
+                size: module.size,
+            })
+            .collect())
+    }
+
+    /// Looks up a symbol in the ELF file of a module, or in the exports of its PE image when it is
+    /// loaded by Wine
+    fn find_symbol(&self, module: &str, symbol: &str) -> Result<Option<RemoteAddress>> {
+        let Some(module_entry) = ModuleSnapshot::new(self.process_id)?
+            .find(|entry| entry.name.eq_ignore_ascii_case(module))
+        else {
+            return Ok(None);
+        };
+
+        match elf::symbol_offset(&module_entry.path, symbol) {
+            Ok(offset) => Ok(offset
+                .map(|offset| RemoteAddress::new(module_entry.base.wrapping_add(offset as usize)))),
+            Err(ElfError::NotElf) => find_export(self, module, symbol),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns the architecture of the process, based on the ELF header of its executable
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        if let Some(arch) = self.arch.get() {
+            return Ok(*arch);
+        }
+
+        let arch = self.read_arch()?;
+        Ok(*self.arch.get_or_init(|| arch))
+    }
+}
+
+/// Whether a failed `process_vm_readv`/`process_vm_writev` should be retried through `/proc/<pid>/mem`
+fn should_fall_back(err: &std::io::Error) -> bool {
+    matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM))
+}
+
+/// Maps an OS error for a remote address into an [Error]
+fn map_error(address: RemoteAddress, err: std::io::Error) -> Error {
+    match err.raw_os_error() {
+        Some(libc::EFAULT | libc::EIO) => Error::InvalidAddress(address),
+        _ => Error::Io(err),
+    }
+}
+
+/// Repeats `f` until `len` bytes have been transferred, returning how far it got.
+///
+/// `f` receives the number of bytes transferred so far. An error is only returned when nothing
+/// could be transferred at all, to match the semantics of `process_vm_readv`.
+fn transfer_partial(
+    address: RemoteAddress,
+    len: usize,
+    mut f: impl FnMut(usize) -> std::io::Result<usize>,
+) -> Result<usize> {
+    let mut done = 0;
+
+    while done < len {
+        match f(done) {
+            Ok(0) => break,
+            Ok(n) => done += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) if done == 0 => return Err(map_error(address, err)),
+            Err(_) => break,
+        }
+    }
+
+    Ok(done)
+}
+
+/// A single line of `/proc/<pid>/maps`
+struct Mapping {
+    start: usize,
+    end: usize,
+    perms: [u8; 4],
+    offset: u64,
+    inode: u64,
+    /// The file path, or a pseudo-path such as `[heap]`
+    path: Option<PathBuf>,
+}
+
+/// Parses `/proc/<pid>/maps`, see proc_pid_maps(5)
+fn read_maps(process_id: u32) -> Result<Vec<Mapping>> {
+    let maps = std::fs::read_to_string(format!("/proc/{process_id}/maps"))?;
+
+    maps.lines()
+        .map(|line| parse_mapping(line).ok_or(Error::InvalidString))
+        .collect()
+}
+
+fn parse_mapping(line: &str) -> Option<Mapping> {
+    let mut fields = line.splitn(6, ' ');
+
+    let (start, end) = fields.next()?.split_once('-')?;
+    let perms = fields.next()?.as_bytes().try_into().ok()?;
+    let offset = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let _dev = fields.next()?;
+    let inode = fields.next()?.parse().ok()?;
+    let path = fields
+        .next()
+        .map(|path| path.trim_start())
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(path.strip_suffix(" (deleted)").unwrap_or(path)));
+
+    Some(Mapping {
+        start: usize::from_str_radix(start, 16).ok()?,
+        end: usize::from_str_radix(end, 16).ok()?,
+        perms,
+        offset,
+        inode,
+        path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    use super::*;
+
+    #[test]
+    fn symbols_of_loaded_modules() {
+        // Linked at 0x10000, so the base of the module is not where its symbols are relative to
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/libsymbols.so");
+        let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+
+        let (handle, show_line) = unsafe {
+            let handle = libc::dlopen(path.as_ptr(), libc::RTLD_NOW);
+            assert!(!handle.is_null());
+            (handle, libc::dlsym(handle, c"show_line".as_ptr()))
+        };
+        assert!(!show_line.is_null());
+
+        let process = Process::open(std::process::id()).unwrap();
+        let show_line = RemoteAddress::new(show_line as usize);
+        assert_eq!(
+            process.find_symbol("libsymbols.so", "show_line").unwrap(),
+            Some(show_line)
+        );
+        // Found in the debug file, 12 bytes before show_line
+        assert_eq!(
+            process.find_symbol("libsymbols.so", "count_line").unwrap(),
+            Some(show_line - 0xC)
+        );
+        assert_eq!(
+            process.find_symbol("libsymbols.so", "missing").unwrap(),
+            None
+        );
+        assert_eq!(
+            process.find_symbol("libmissing.so", "show_line").unwrap(),
+            None
+        );
+
+        unsafe { libc::dlclose(handle) };
+    }
+}
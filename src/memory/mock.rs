@@ -0,0 +1,415 @@
+use std::{cell::RefCell, collections::BTreeMap, ops::Range};
+
+use super::{
+    Error, MemoryRegion, MemorySource, ModuleInfo, ProcessArchitecture, Protection, RegionKind,
+    RegionState, RemoteAddress, Result,
+};
+
+/// A failure to inject into accesses of a [MockProcess]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Any access touching the range fails as if the process denied access
+    AccessDenied,
+    /// Accesses stop at the start of the range, as if the page was unmapped during the call
+    Partial,
+}
+
+struct MockRegion {
+    data: Vec<u8>,
+    protection: Protection,
+}
+
+/// A fake process backed by a sparse set of in-memory regions.
+///
+/// Reads and writes behave like they do on a real process: they stop at the first byte that is
+/// unmapped or lacks the required [Protection], and fail only if no byte could be transferred.
+#[derive(Default)]
+pub struct MockProcess {
+    regions: RefCell<BTreeMap<usize, MockRegion>>,
+    faults: Vec<(Range<RemoteAddress>, Fault)>,
+    modules: Vec<ModuleInfo>,
+    arch: Option<ProcessArchitecture>,
+}
+
+impl MockProcess {
+    /// Creates a process without any mapped memory
+    pub fn new() -> MockProcess {
+        MockProcess::default()
+    }
+
+    /// Maps `data` at `base` with the given protection
+    ///
+    /// Panics if the region overlaps an already mapped region.
+    pub fn map(&mut self, base: RemoteAddress, data: impl Into<Vec<u8>>, protection: Protection) {
+        let data = data.into();
+        let base = base.get();
+        let end = base + data.len();
+
+        let regions = self.regions.get_mut();
+        assert!(
+            regions
+                .range(..end)
+                .next_back()
+                .is_none_or(|(other, region)| other + region.data.len() <= base),
+            "region {base:#x}..{end:#x} overlaps an existing region"
+        );
+
+        regions.insert(base, MockRegion { data, protection });
+    }
+
+    /// Maps `size` zeroed bytes at `base` with the given protection
+    pub fn map_zeroed(&mut self, base: RemoteAddress, size: usize, protection: Protection) {
+        self.map(base, vec![0; size], protection);
+    }
+
+    /// Removes the region starting at `base`, returning its contents
+    pub fn unmap(&mut self, base: RemoteAddress) -> Option<Vec<u8>> {
+        self.regions
+            .get_mut()
+            .remove(&base.get())
+            .map(|region| region.data)
+    }
+
+    /// Changes the protection of the region starting at `base`
+    pub fn protect(&mut self, base: RemoteAddress, protection: Protection) {
+        if let Some(region) = self.regions.get_mut().get_mut(&base.get()) {
+            region.protection = protection;
+        }
+    }
+
+    /// Makes every access touching `range` fail with `fault`
+    pub fn inject_fault(&mut self, range: Range<RemoteAddress>, fault: Fault) {
+        self.faults.push((range, fault));
+    }
+
+    /// Removes all injected faults
+    pub fn clear_faults(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Adds a module to the list returned by [modules](MemorySource::modules)
+    pub fn add_module(&mut self, name: impl Into<String>, base: RemoteAddress, size: usize) {
+        self.modules.push(ModuleInfo {
+            name: name.into(),
+            base,
+            size,
+        });
+    }
+
+    /// Makes the process pretend to be of another architecture, which changes the size of pointers
+    pub fn set_arch(&mut self, arch: ProcessArchitecture) {
+        self.arch = Some(arch);
+    }
+
+    /// Returns the number of bytes that may be accessed at `address`, limited to `len` bytes
+    fn accessible(
+        &self,
+        address: RemoteAddress,
+        len: usize,
+        allowed: impl Fn(Protection) -> bool,
+    ) -> Result<usize> {
+        let address = address.get();
+        let end = address.saturating_add(len);
+
+        // Faults cut the access short, or deny it entirely
+        let mut limit = end;
+        for (range, fault) in &self.faults {
+            if range.start.get() < end && address < range.end.get() {
+                match fault {
+                    Fault::AccessDenied => {
+                        return Err(Error::Io(std::io::ErrorKind::PermissionDenied.into()))
+                    }
+                    Fault::Partial => limit = limit.min(range.start.get().max(address)),
+                }
+            }
+        }
+
+        // Walk adjacent regions until we run into a gap or a protected region
+        let regions = self.regions.borrow();
+        let mut cursor = address;
+        while cursor < limit {
+            match regions.range(..=cursor).next_back() {
+                Some((base, region))
+                    if cursor < base + region.data.len() && allowed(region.protection) =>
+                {
+                    cursor = base + region.data.len();
+                }
+                _ => break,
+            }
+        }
+
+        match cursor.min(limit) - address {
+            0 if len > 0 => Err(Error::InvalidAddress(RemoteAddress::new(address))),
+            accessible => Ok(accessible),
+        }
+    }
+
+    /// Calls `f` for each part of the regions covering `address..address + len`, in order
+    fn for_each_chunk(
+        &self,
+        address: RemoteAddress,
+        len: usize,
+        mut f: impl FnMut(&mut [u8], usize),
+    ) {
+        let address = address.get();
+        let mut regions = self.regions.borrow_mut();
+        let mut cursor = address;
+
+        while cursor < address + len {
+            let (base, region) = regions
+                .range_mut(..=cursor)
+                .next_back()
+                .expect("range should have been checked to be mapped");
+
+            let start = cursor - base;
+            let count = (region.data.len() - start).min(address + len - cursor);
+            f(&mut region.data[start..start + count], cursor - address);
+
+            cursor += count;
+        }
+    }
+}
+
+impl MemorySource for MockProcess {
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        let len = self.accessible(address, buffer.len(), |prot| prot.read)?;
+
+        self.for_each_chunk(address, len, |chunk, offset| {
+            buffer[offset..offset + chunk.len()].copy_from_slice(chunk);
+        });
+
+        Ok(len)
+    }
+
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+        let len = self.accessible(address, buffer.len(), |prot| prot.write)?;
+
+        self.for_each_chunk(address, len, |chunk, offset| {
+            chunk.copy_from_slice(&buffer[offset..offset + chunk.len()]);
+        });
+
+        Ok(len)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        Ok(self
+            .regions
+            .borrow()
+            .iter()
+            .map(|(base, region)| MemoryRegion {
+                base: RemoteAddress::new(*base),
+                size: region.data.len(),
+                protection: region.protection,
+                state: RegionState::Committed,
+                kind: RegionKind::Private,
+            })
+            .collect())
+    }
+
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        Ok(self.modules.clone())
+    }
+
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        Ok(self.arch.unwrap_or_else(ProcessArchitecture::host))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::Encoding;
+
+    const PAGE: usize = 0x1000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    #[test]
+    fn read_stops_at_unmapped_page() {
+        let mut process = MockProcess::new();
+        process.map(addr(0x10000), vec![0xAA; PAGE], Protection::READ);
+        process.map(addr(0x10000 + PAGE), vec![0xBB; PAGE], Protection::READ);
+
+        // Adjacent regions are read as one
+        let mut buffer = vec![0; PAGE * 2];
+        assert_eq!(process.read(addr(0x10000), &mut buffer).unwrap(), PAGE * 2);
+        assert_eq!(buffer[PAGE - 1..PAGE + 1], [0xAA, 0xBB]);
+
+        // The third page is not mapped
+        let mut buffer = vec![0; PAGE * 2];
+        assert_eq!(
+            process.read(addr(0x10000 + PAGE + 8), &mut buffer).unwrap(),
+            PAGE - 8
+        );
+        assert!(buffer[..PAGE - 8].iter().all(|&b| b == 0xBB));
+        assert!(buffer[PAGE - 8..].iter().all(|&b| b == 0));
+
+        assert!(matches!(
+            process.read(addr(0x10000 + PAGE * 2), &mut buffer),
+            Err(Error::InvalidAddress(address)) if address == addr(0x10000 + PAGE * 2)
+        ));
+        assert!(matches!(
+            process.read_exact(addr(0x10000 + PAGE), &mut buffer),
+            Err(Error::IncompleteRead { expected, read }) if expected == PAGE * 2 && read == PAGE
+        ));
+    }
+
+    #[test]
+    fn protection_is_respected() {
+        let mut process = MockProcess::new();
+        process.map(addr(0x10000), vec![1; PAGE], Protection::READ);
+        process.map(addr(0x10000 + PAGE), vec![2; PAGE], Protection::NONE);
+
+        let mut buffer = [0; 16];
+        assert!(matches!(
+            process.read(addr(0x10000 + PAGE), &mut buffer),
+            Err(Error::InvalidAddress(_))
+        ));
+        assert_eq!(
+            process.read(addr(0x10000 + PAGE - 4), &mut buffer).unwrap(),
+            4
+        );
+
+        // Writes need write access
+        assert!(process.write_u32(addr(0x10000), 5).is_err());
+        assert_eq!(process.read_u32(addr(0x10000)).unwrap(), 0x0101_0101);
+
+        process.protect(addr(0x10000 + PAGE), Protection::READ_WRITE);
+        process.write_u32(addr(0x10000 + PAGE), 5).unwrap();
+        assert_eq!(process.read_u32(addr(0x10000 + PAGE)).unwrap(), 5);
+    }
+
+    #[test]
+    fn partial_writes_are_errors() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(0x10000), PAGE, Protection::READ_WRITE);
+        process.map_zeroed(addr(0x10000 + PAGE), PAGE, Protection::READ);
+
+        let end = addr(0x10000 + PAGE - 4);
+        assert_eq!(process.write(end, &[1; 8]).unwrap(), 4);
+        assert!(matches!(
+            process.write_exact(end, &[2; 8]),
+            Err(Error::IncompleteWrite {
+                expected: 8,
+                written: 4
+            })
+        ));
+        assert!(matches!(
+            process.write_u64(end, u64::MAX),
+            Err(Error::IncompleteWrite { .. })
+        ));
+        assert!(matches!(
+            process.write_ptr(end, addr(usize::MAX)),
+            Err(Error::IncompleteWrite { .. })
+        ));
+        assert!(matches!(
+            process.write_slice(end, &[3u16; 4]),
+            Err(Error::IncompleteWrite { .. })
+        ));
+
+        // What fit before the read-only page is still written, as the OS would
+        assert_eq!(process.read_u32(end).unwrap(), 0x0003_0003);
+        process.write_slice(end, &[4u16; 2]).unwrap();
+        assert_eq!(process.read_u32(end).unwrap(), 0x0004_0004);
+    }
+
+    #[test]
+    fn injected_faults() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(0x10000), PAGE * 2, Protection::READ_WRITE);
+
+        let mut buffer = [0; 0x100];
+        process.inject_fault(addr(0x10080)..addr(0x10100), Fault::Partial);
+        assert_eq!(process.read(addr(0x10000), &mut buffer).unwrap(), 0x80);
+        assert_eq!(process.write(addr(0x10040), &buffer).unwrap(), 0x40);
+        assert!(matches!(
+            process.read(addr(0x10080), &mut buffer),
+            Err(Error::InvalidAddress(_))
+        ));
+
+        process.inject_fault(addr(0x10800)..addr(0x10801), Fault::AccessDenied);
+        assert!(matches!(
+            process.read(addr(0x10780), &mut buffer),
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::PermissionDenied
+        ));
+        // Accesses that do not touch the range are unaffected
+        assert_eq!(process.read(addr(0x10900), &mut buffer).unwrap(), 0x100);
+
+        process.clear_faults();
+        assert_eq!(process.read(addr(0x10780), &mut buffer).unwrap(), 0x100);
+    }
+
+    #[test]
+    fn pointer_chain() {
+        for arch in [ProcessArchitecture::X86, ProcessArchitecture::X64] {
+            let mut process = MockProcess::new();
+            process.set_arch(arch);
+            process.map(addr(0x10000), vec![0xFF; PAGE], Protection::READ_WRITE);
+            process.map_zeroed(addr(0x20000), PAGE, Protection::READ_WRITE);
+
+            // 0x10010 -> 0x20000, +0x20 -> 0x20100, +0x8 -> text
+            process.write_ptr(addr(0x10010), addr(0x20000)).unwrap();
+            process.write_ptr(addr(0x20020), addr(0x20100)).unwrap();
+            process
+                .write(addr(0x20108), "こんにちは".as_bytes())
+                .unwrap();
+
+            let first = process.read_ptr(addr(0x10010)).unwrap();
+            let second = process.read_ptr(first + 0x20).unwrap();
+            assert_eq!(second, addr(0x20100));
+            assert_eq!(
+                process.read_text(second + 8, 15, Encoding::Utf8).unwrap(),
+                "こんにちは"
+            );
+
+            // Only the width of the target's pointers is written
+            let expected = match arch {
+                ProcessArchitecture::X86 => 0xFFFF_FFFF_0002_0000,
+                ProcessArchitecture::X64 => 0x20000,
+            };
+            assert_eq!(process.read_u64(addr(0x10010)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn text_decoding() {
+        let mut process = MockProcess::new();
+        process.map(
+            addr(0x10000),
+            [
+                b"\x82\xb1\x82\xf1".as_slice(),
+                &[0x53, 0x30, 0x93, 0x30],
+                b"\xff\xfe",
+            ]
+            .concat(),
+            Protection::READ,
+        );
+
+        assert_eq!(
+            process
+                .read_text(addr(0x10000), 4, Encoding::ShiftJis)
+                .unwrap(),
+            "こん"
+        );
+        assert_eq!(
+            process
+                .read_text(addr(0x10004), 4, Encoding::Utf16Le)
+                .unwrap(),
+            "こん"
+        );
+        assert!(matches!(
+            process.read_text(addr(0x10008), 2, Encoding::Utf8),
+            Err(Error::InvalidText(Encoding::Utf8))
+        ));
+        assert_eq!(
+            process
+                .read_text_lossy(addr(0x10008), 2, Encoding::Utf8)
+                .unwrap(),
+            "\u{FFFD}\u{FFFD}"
+        );
+        // Running off the end of the mapping is not silently truncated
+        assert!(process.read_text(addr(0x10008), 4, Encoding::Utf8).is_err());
+    }
+}
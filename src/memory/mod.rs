@@ -0,0 +1,658 @@
+#![allow(dead_code)]
+
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bytemuck::Pod;
+use thiserror::Error;
+
+use crate::{
+    elf::ElfError,
+    pe::{export::ExportTarget, version::VersionInfo, PeError, PeImage},
+    scan::Pattern,
+    text::Encoding,
+};
+
+mod address;
+pub mod cache;
+#[cfg(target_os = "linux")]
+mod linux;
+pub mod mock;
+pub mod pointer;
+#[cfg(windows)]
+mod win32;
+
+// Nothing in the binary uses the backend outside of Windows yet
+#[cfg(target_os = "linux")]
+#[allow(unused_imports)]
+pub use linux::*;
+#[cfg(windows)]
+pub use win32::*;
+
+pub use address::RemoteAddress;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[cfg(windows)]
+    #[error("An internal windows error occurred")]
+    Windows(#[from] windows::core::Error),
+    #[error("An I/O error occurred")]
+    Io(#[from] std::io::Error),
+    #[error("The OS returned an invalid string")]
+    InvalidString,
+    #[error("The text is not valid {0}")]
+    InvalidText(Encoding),
+    #[error("The address {0} is not accessible")]
+    InvalidAddress(RemoteAddress),
+    #[error("Only {read} out of {expected} bytes could be read")]
+    IncompleteRead { expected: usize, read: usize },
+    #[error("Only {written} out of {expected} bytes could be written")]
+    IncompleteWrite { expected: usize, written: usize },
+    #[error("The memory is read-only")]
+    ReadOnly,
+    #[error("Failed to parse the executable")]
+    Pe(#[from] PeError),
+    #[error("Failed to parse the ELF file")]
+    Elf(#[from] ElfError),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An address space that can be read from and written to, such as the memory of another process.
+///
+/// Only [read](Self::read) and [write](Self::write) need to be implemented, everything else is
+/// built on top of them. This lets the same code run against a [Process] as well as an
+/// in-process buffer.
+pub trait MemorySource {
+    /// Reads the memory at an address into a buffer and returns the number of bytes read.
+    ///
+    /// A read that runs into inaccessible memory stops there, and fails with
+    /// [Error::InvalidAddress] if not even the first byte could be read.
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize>;
+
+    /// Writes the contents of a buffer to a given address and returns the number of bytes written
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize>;
+
+    /// Returns the regions of the address space in ascending order
+    fn regions(&self) -> Result<Vec<MemoryRegion>>;
+
+    /// Returns the modules loaded into the address space, if it has any
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        Ok(Vec::new())
+    }
+
+    /// Returns the architecture of the address space, which decides the size of pointers.
+    ///
+    /// Defaults to the architecture we are running on.
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        Ok(ProcessArchitecture::host())
+    }
+
+    /// Returns the address of a symbol defined by a loaded module, such as a function, or `None`
+    /// if either of them does not exist.
+    ///
+    /// Defaults to the functions exported by the PE image of the module.
+    fn find_symbol(&self, module: &str, symbol: &str) -> Result<Option<RemoteAddress>> {
+        find_export(&self, module, symbol)
+    }
+
+    /// Reads the memory at an address into a buffer, failing unless the whole buffer was filled
+    fn read_exact(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<()> {
+        let read = self.read(address, buffer)?;
+
+        if read == buffer.len() {
+            Ok(())
+        } else {
+            Err(Error::IncompleteRead {
+                expected: buffer.len(),
+                read,
+            })
+        }
+    }
+
+    /// Writes a buffer to an address, failing unless all of it was written
+    fn write_exact(&self, address: RemoteAddress, buffer: &[u8]) -> Result<()> {
+        let written = self.write(address, buffer)?;
+
+        if written == buffer.len() {
+            Ok(())
+        } else {
+            Err(Error::IncompleteWrite {
+                expected: buffer.len(),
+                written,
+            })
+        }
+    }
+
+    /// Reads the memory at an address and returns a buffer containing the data
+    fn read_array<const N: usize>(&self, address: RemoteAddress) -> Result<[u8; N]> {
+        let mut buf = [0; N];
+
+        self.read_exact(address, &mut buf)?;
+
+        Ok(buf)
+    }
+
+    /* READ & WRITE EXTENSIONS */
+    /// Reads a pointer with the width and byte order of the [architecture](Self::arch)
+    fn read_ptr(&self, address: RemoteAddress) -> Result<RemoteAddress> {
+        let arch = self.arch()?;
+
+        let mut buf = [0; 8];
+        let buf = &mut buf[..arch.pointer_size()];
+        self.read_exact(address, buf)?;
+
+        let value = arch.endianness().read_uint(buf);
+
+        // Can only fail if the target has wider pointers than we do
+        usize::try_from(value)
+            .map(RemoteAddress::new)
+            .map_err(|_| Error::InvalidAddress(address))
+    }
+
+    /// Writes a pointer with the width and byte order of the [architecture](Self::arch)
+    fn write_ptr(&self, address: RemoteAddress, value: RemoteAddress) -> Result<()> {
+        let arch = self.arch()?;
+
+        let mut buf = [0; 8];
+        let buf = &mut buf[..arch.pointer_size()];
+        arch.endianness().write_uint(buf, value.get() as u64);
+
+        self.write_exact(address, buf)
+    }
+
+    /// Reads a value of any plain old data type.
+    ///
+    /// The address does not have to be aligned, and the type may be `#[repr(C)]` as well as
+    /// `#[repr(C, packed)]`. Numbers are read in the byte order of the host.
+    fn read_pod<T: Pod>(&self, address: RemoteAddress) -> Result<T> {
+        let mut value = T::zeroed();
+
+        self.read_exact(address, bytemuck::bytes_of_mut(&mut value))?;
+
+        Ok(value)
+    }
+
+    /// Reads `count` consecutive values of a plain old data type into an aligned buffer
+    fn read_slice<T: Pod>(&self, address: RemoteAddress, count: usize) -> Result<Vec<T>> {
+        let mut values = vec![T::zeroed(); count];
+
+        self.read_exact(address, bytemuck::cast_slice_mut(&mut values))?;
+
+        Ok(values)
+    }
+
+    /// Writes a value of any plain old data type, see [read_pod](Self::read_pod)
+    fn write_pod<T: Pod>(&self, address: RemoteAddress, value: &T) -> Result<()> {
+        self.write_exact(address, bytemuck::bytes_of(value))
+    }
+
+    /// Writes consecutive values of a plain old data type
+    fn write_slice<T: Pod>(&self, address: RemoteAddress, values: &[T]) -> Result<()> {
+        self.write_exact(address, bytemuck::cast_slice(values))
+    }
+
+    /// Reads `len` bytes of text, failing if it is not valid in the encoding
+    fn read_text(&self, address: RemoteAddress, len: usize, encoding: Encoding) -> Result<String> {
+        let mut buf = vec![0; len];
+
+        self.read_exact(address, &mut buf)?;
+
+        encoding.decode(&buf).ok_or(Error::InvalidText(encoding))
+    }
+
+    /// Reads `len` bytes of text, replacing invalid sequences with U+FFFD
+    fn read_text_lossy(
+        &self,
+        address: RemoteAddress,
+        len: usize,
+        encoding: Encoding,
+    ) -> Result<String> {
+        let mut buf = vec![0; len];
+
+        self.read_exact(address, &mut buf)?;
+
+        Ok(encoding.decode_lossy(&buf))
+    }
+
+    fn read_u64(&self, address: RemoteAddress) -> Result<u64> {
+        self.read_pod(address)
+    }
+
+    fn read_i64(&self, address: RemoteAddress) -> Result<i64> {
+        self.read_pod(address)
+    }
+
+    fn read_f64(&self, address: RemoteAddress) -> Result<f64> {
+        self.read_pod(address)
+    }
+
+    fn read_u32(&self, address: RemoteAddress) -> Result<u32> {
+        self.read_pod(address)
+    }
+
+    fn read_i32(&self, address: RemoteAddress) -> Result<i32> {
+        self.read_pod(address)
+    }
+
+    fn read_f32(&self, address: RemoteAddress) -> Result<f32> {
+        self.read_pod(address)
+    }
+
+    fn read_u16(&self, address: RemoteAddress) -> Result<u16> {
+        self.read_pod(address)
+    }
+
+    fn read_i16(&self, address: RemoteAddress) -> Result<i16> {
+        self.read_pod(address)
+    }
+
+    fn read_u8(&self, address: RemoteAddress) -> Result<u8> {
+        self.read_pod(address)
+    }
+
+    fn read_i8(&self, address: RemoteAddress) -> Result<i8> {
+        self.read_pod(address)
+    }
+
+    fn read_bool(&self, address: RemoteAddress) -> Result<bool> {
+        Ok(self.read_u8(address)? != 0)
+    }
+
+    fn write_u64(&self, address: RemoteAddress, value: u64) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_i64(&self, address: RemoteAddress, value: i64) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_f64(&self, address: RemoteAddress, value: f64) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_u32(&self, address: RemoteAddress, value: u32) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_i32(&self, address: RemoteAddress, value: i32) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_f32(&self, address: RemoteAddress, value: f32) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_u16(&self, address: RemoteAddress, value: u16) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_i16(&self, address: RemoteAddress, value: i16) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_u8(&self, address: RemoteAddress, value: u8) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_i8(&self, address: RemoteAddress, value: i8) -> Result<()> {
+        self.write_pod(address, &value)
+    }
+
+    fn write_bool(&self, address: RemoteAddress, value: bool) -> Result<()> {
+        self.write_u8(address, if value { 0x01 } else { 0x00 })
+    }
+
+    /* SCANNING */
+    /// Returns the address of every match of a byte pattern in the readable memory
+    fn scan_pattern(&self, pattern: &Pattern) -> Result<Vec<RemoteAddress>> {
+        self.scan_pattern_in(pattern, RemoteAddress::NULL..RemoteAddress::new(usize::MAX))
+    }
+
+    /// Returns the address of every match of a byte pattern in part of the memory, e.g. the
+    /// [range](ModuleInfo::range) of a module
+    fn scan_pattern_in(
+        &self,
+        pattern: &Pattern,
+        range: Range<RemoteAddress>,
+    ) -> Result<Vec<RemoteAddress>> {
+        pattern.scan_range(self, range)
+    }
+}
+
+impl<T: MemorySource + ?Sized> MemorySource for &T {
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        (**self).read(address, buffer)
+    }
+
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+        (**self).write(address, buffer)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        (**self).regions()
+    }
+
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        (**self).modules()
+    }
+
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        (**self).arch()
+    }
+
+    fn find_symbol(&self, module: &str, symbol: &str) -> Result<Option<RemoteAddress>> {
+        (**self).find_symbol(module, symbol)
+    }
+}
+
+/// A buffer in our own memory, where the address is the offset into the slice.
+///
+/// Reads past the end of the buffer are truncated. Writing is not supported.
+impl MemorySource for [u8] {
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        let offset = address.get();
+
+        let available = self
+            .get(offset..)
+            .filter(|available| !available.is_empty() || buffer.is_empty())
+            .ok_or(Error::InvalidAddress(address))?;
+
+        let len = available.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&available[..len]);
+
+        Ok(len)
+    }
+
+    fn write(&self, _address: RemoteAddress, _buffer: &[u8]) -> Result<usize> {
+        Err(Error::ReadOnly)
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        Ok(vec![MemoryRegion {
+            base: RemoteAddress::NULL,
+            size: self.len(),
+            protection: Protection::READ,
+            state: RegionState::Committed,
+            kind: RegionKind::Private,
+        }])
+    }
+}
+
+/// A module loaded into an address space, independent of the platform it was read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub base: RemoteAddress,
+    pub size: usize,
+}
+
+impl ModuleInfo {
+    /// Returns the addresses the module is loaded at
+    pub fn range(&self) -> Range<RemoteAddress> {
+        self.base..self.base + self.size
+    }
+
+    /// Parses the PE image of the module as it is loaded in an address space
+    pub fn read_image<S: MemorySource>(&self, source: &S) -> Result<PeImage> {
+        PeImage::read(source, self.base)
+    }
+}
+
+/// The access allowed to a range of memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Protection {
+    pub read: bool,
+    pub write: bool,
+    pub execute: bool,
+}
+
+impl Protection {
+    pub const NONE: Protection = Protection {
+        read: false,
+        write: false,
+        execute: false,
+    };
+    pub const READ: Protection = Protection {
+        read: true,
+        ..Self::NONE
+    };
+    pub const READ_WRITE: Protection = Protection {
+        write: true,
+        ..Self::READ
+    };
+    pub const READ_EXECUTE: Protection = Protection {
+        execute: true,
+        ..Self::READ
+    };
+    pub const READ_WRITE_EXECUTE: Protection = Protection {
+        write: true,
+        ..Self::READ_EXECUTE
+    };
+}
+
+/// A range of pages in an address space that share the same attributes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: RemoteAddress,
+    pub size: usize,
+    pub protection: Protection,
+    pub state: RegionState,
+    pub kind: RegionKind,
+}
+
+impl MemoryRegion {
+    /// Returns the first address after the region
+    pub fn end(&self) -> RemoteAddress {
+        self.base + self.size
+    }
+
+    /// Returns whether the address lies within the region
+    pub fn contains(&self, address: RemoteAddress) -> bool {
+        (self.base..self.end()).contains(&address)
+    }
+
+    /// Returns whether the region is backed by memory that we are allowed to read
+    pub fn is_readable(&self) -> bool {
+        self.state == RegionState::Committed && self.protection.read
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionState {
+    /// The region is backed by memory
+    Committed,
+    /// The addresses are reserved, but not backed by memory
+    Reserved,
+    /// The addresses are not in use
+    Free,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegionKind {
+    /// The region belongs to an executable image, e.g. an exe, dll or shared object
+    Image(Option<PathBuf>),
+    /// The region is private to the process, e.g. the heap or the stack
+    Private,
+    /// The region is a view of a mapped file, or of shared memory if there is no path
+    Mapped(Option<PathBuf>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessArchitecture {
+    X64,
+    X86,
+}
+
+impl ProcessArchitecture {
+    /// Returns the architecture we are running on
+    pub fn host() -> ProcessArchitecture {
+        if cfg!(target_pointer_width = "64") {
+            ProcessArchitecture::X64
+        } else {
+            ProcessArchitecture::X86
+        }
+    }
+
+    /// Returns the size of a pointer in bytes
+    pub fn pointer_size(&self) -> usize {
+        match self {
+            Self::X64 => 8,
+            Self::X86 => 4,
+        }
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        match self {
+            Self::X64 | Self::X86 => Endianness::Little,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Endianness {
+    /// Reads an unsigned integer of up to 8 bytes
+    pub fn read_uint(&self, bytes: &[u8]) -> u64 {
+        assert!(bytes.len() <= 8, "integers are at most 8 bytes");
+
+        let mut buf = [0; 8];
+        match self {
+            Self::Little => {
+                buf[..bytes.len()].copy_from_slice(bytes);
+                u64::from_le_bytes(buf)
+            }
+            Self::Big => {
+                buf[8 - bytes.len()..].copy_from_slice(bytes);
+                u64::from_be_bytes(buf)
+            }
+        }
+    }
+
+    /// Writes the lowest `bytes.len()` bytes of an unsigned integer of up to 8 bytes
+    pub fn write_uint(&self, bytes: &mut [u8], value: u64) {
+        assert!(bytes.len() <= 8, "integers are at most 8 bytes");
+
+        let len = bytes.len();
+        match self {
+            Self::Little => bytes.copy_from_slice(&value.to_le_bytes()[..len]),
+            Self::Big => bytes.copy_from_slice(&value.to_be_bytes()[8 - len..]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileInfoField {
+    Comments,
+    InternalName,
+    ProductName,
+    CompanyName,
+    LegalCopyright,
+    ProductVersion,
+    FileDescription,
+    LegalTrademarks,
+    PrivateBuild,
+    FileVersion,
+    OriginalFilename,
+    SpecialBuild,
+}
+
+impl FileInfoField {
+    pub fn field_name(&self) -> &'static str {
+        match self {
+            Self::Comments => "Comments",
+            Self::InternalName => "InternalName",
+            Self::ProductName => "ProductName",
+            Self::CompanyName => "CompanyName",
+            Self::LegalCopyright => "LegalCopyright",
+            Self::ProductVersion => "ProductVersion",
+            Self::FileDescription => "FileDescription",
+            Self::LegalTrademarks => "LegalTrademarks",
+            Self::PrivateBuild => "PrivateBuild",
+            Self::FileVersion => "FileVersion",
+            Self::OriginalFilename => "OriginalFilename",
+            Self::SpecialBuild => "SpecialBuild",
+        }
+    }
+}
+
+/// The strings of an executable's version information in one of its languages
+pub struct FileInfo {
+    info: Arc<VersionInfo>,
+    language: u16,
+    code_page: u16,
+}
+
+impl FileInfo {
+    pub fn language(&self) -> u16 {
+        self.language
+    }
+
+    pub fn code_page(&self) -> u16 {
+        self.code_page
+    }
+
+    pub fn get_string(&self, field: FileInfoField) -> Option<&str> {
+        self.info
+            .get_string(self.language, self.code_page, field.field_name())
+    }
+}
+
+/// Looks up a function exported by the PE image of a loaded module. Forwarded exports are not
+/// followed, since they live in another module.
+fn find_export<S: MemorySource>(
+    source: &S,
+    module: &str,
+    symbol: &str,
+) -> Result<Option<RemoteAddress>> {
+    let Some(module) = source
+        .modules()?
+        .into_iter()
+        .find(|info| info.name.eq_ignore_ascii_case(module))
+    else {
+        return Ok(None);
+    };
+
+    Ok(match module.read_image(source)?.export(symbol)? {
+        Some(export) => match export.target {
+            ExportTarget::Rva(rva) => Some(module.base + rva as usize),
+            ExportTarget::Forwarder(_) => None,
+        },
+        None => None,
+    })
+}
+
+/// Reads the version information of an executable in every language it lists, or none if it
+/// does not have any
+pub fn read_file_info(path: &Path) -> Result<Vec<FileInfo>> {
+    let Some(info) = PeImage::open(path)?.version_info()? else {
+        return Ok(Vec::new());
+    };
+    let info = Arc::new(info);
+
+    Ok(info
+        .languages()
+        .into_iter()
+        .map(|entry| FileInfo {
+            info: info.clone(),
+            language: entry.language,
+            code_page: entry.code_page,
+        })
+        .collect())
+}
+
+pub(crate) mod ffi {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C, packed)]
+    pub struct TranslationEntry {
+        pub language: u16,
+        pub code_page: u16,
+    }
+}
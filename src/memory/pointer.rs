@@ -0,0 +1,449 @@
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use super::{MemorySource, RemoteAddress};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("The pointer path is empty")]
+    Empty,
+    #[error("The module name is missing a closing quote")]
+    UnterminatedQuote,
+    #[error("{0:?} is not a valid hexadecimal offset")]
+    InvalidOffset(String),
+    #[error("The name of the module or symbol is missing")]
+    MissingName,
+}
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("The module {0:?} is not loaded")]
+    ModuleNotFound(String),
+    #[error("Failed to read the pointer at {address} (hop {hop})")]
+    Read {
+        hop: usize,
+        address: RemoteAddress,
+        #[source]
+        source: super::Error,
+    },
+    #[error("The offset of hop {hop} moves {address} out of the address space")]
+    Overflow { hop: usize, address: RemoteAddress },
+    #[error("Failed to list the modules")]
+    Modules(#[source] super::Error),
+    #[error("The symbol {symbol:?} is not defined by the module {module:?}")]
+    SymbolNotFound { module: String, symbol: String },
+    #[error("Failed to look up the symbols of a module")]
+    Symbols(#[source] super::Error),
+}
+
+/// Where a [PointerPath] starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerBase {
+    /// An offset from the base address of a module
+    Module { name: String, offset: isize },
+    /// An offset from a symbol defined by a module, such as `libfoo.so!render_text+0x10`
+    Symbol {
+        module: String,
+        symbol: String,
+        offset: isize,
+    },
+    /// A fixed address
+    Absolute(RemoteAddress),
+}
+
+/// A chain of pointers, such as `"game.exe"+0x1A2B3C -> +0x10 -> +0x48 -> +0x0`.
+///
+/// Each `->` reads the pointer at the current address and then adds the offset that follows it.
+/// Numbers are hexadecimal, with or without a `0x` prefix, like in Cheat Engine.
+///
+/// The path can also start from a symbol, such as `libfoo.so!render_text+0x10`, which is looked up
+/// with [MemorySource::find_symbol].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointerPath {
+    pub base: PointerBase,
+    pub offsets: Vec<isize>,
+}
+
+impl PointerPath {
+    /// Resolves the base of the path, without following any pointers
+    pub fn resolve_base(&self, source: &impl MemorySource) -> Result<RemoteAddress, ResolveError> {
+        match &self.base {
+            PointerBase::Absolute(address) => Ok(*address),
+            PointerBase::Module { name, offset } => {
+                let module = source
+                    .modules()
+                    .map_err(ResolveError::Modules)?
+                    .into_iter()
+                    .find(|module| module.name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| ResolveError::ModuleNotFound(name.clone()))?;
+
+                module
+                    .base
+                    .checked_offset(*offset)
+                    .ok_or(ResolveError::Overflow {
+                        hop: 0,
+                        address: module.base,
+                    })
+            }
+            PointerBase::Symbol {
+                module,
+                symbol,
+                offset,
+            } => {
+                let address = source
+                    .find_symbol(module, symbol)
+                    .map_err(ResolveError::Symbols)?
+                    .ok_or_else(|| ResolveError::SymbolNotFound {
+                        module: module.clone(),
+                        symbol: symbol.clone(),
+                    })?;
+
+                address
+                    .checked_offset(*offset)
+                    .ok_or(ResolveError::Overflow { hop: 0, address })
+            }
+        }
+    }
+
+    /// Follows the path and returns the final address
+    pub fn resolve(&self, source: &impl MemorySource) -> Result<RemoteAddress, ResolveError> {
+        let mut address = self.resolve_base(source)?;
+
+        for (i, offset) in self.offsets.iter().enumerate() {
+            let hop = i + 1;
+
+            let pointer = source
+                .read_ptr(address)
+                .map_err(|source| ResolveError::Read {
+                    hop,
+                    address,
+                    source,
+                })?;
+
+            address = pointer
+                .checked_offset(*offset)
+                .ok_or(ResolveError::Overflow {
+                    hop,
+                    address: pointer,
+                })?;
+        }
+
+        Ok(address)
+    }
+}
+
+impl FromStr for PointerPath {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split("->").map(str::trim);
+
+        let base = parse_base(parts.next().unwrap_or_default())?;
+        let offsets = parts
+            .map(|part| parse_offset(part).ok_or_else(|| ParseError::InvalidOffset(part.into())))
+            .collect::<Result<_, _>>()?;
+
+        Ok(PointerPath { base, offsets })
+    }
+}
+
+impl fmt::Display for PointerPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.base {
+            PointerBase::Module { name, offset } => {
+                write!(f, "\"{name}\"{}", SignedHex(*offset))?;
+            }
+            PointerBase::Symbol {
+                module,
+                symbol,
+                offset,
+            } => {
+                write!(f, "\"{module}\"!{symbol}{}", SignedHex(*offset))?;
+            }
+            PointerBase::Absolute(address) => write!(f, "{address}")?,
+        }
+
+        for offset in &self.offsets {
+            write!(f, " -> {}", SignedHex(*offset))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats an offset as `+0x10` or `-0x10`
+struct SignedHex(isize);
+
+impl fmt::Display for SignedHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        write!(f, "{sign}{:#X}", self.0.unsigned_abs())
+    }
+}
+
+fn parse_base(s: &str) -> Result<PointerBase, ParseError> {
+    if s.is_empty() {
+        return Err(ParseError::Empty);
+    }
+
+    // "game.exe"+0x1A2B3C or "libfoo.so"!render_text+0x10
+    if let Some(quoted) = s.strip_prefix('"') {
+        let (name, rest) = quoted
+            .split_once('"')
+            .ok_or(ParseError::UnterminatedQuote)?;
+        let rest = rest.trim();
+
+        if let Some(symbol) = rest.strip_prefix('!') {
+            return parse_symbol(name, symbol);
+        }
+
+        let offset = match rest {
+            "" => 0,
+            rest => parse_offset(rest).ok_or_else(|| ParseError::InvalidOffset(rest.into()))?,
+        };
+
+        return Ok(PointerBase::Module {
+            name: name.to_string(),
+            offset,
+        });
+    }
+
+    // libfoo.so!render_text+0x10
+    if let Some((name, symbol)) = s.split_once('!') {
+        return parse_symbol(name.trim(), symbol);
+    }
+
+    // 0x12345678
+    if let Some(address) = parse_hex(s) {
+        return Ok(PointerBase::Absolute(RemoteAddress::new(address)));
+    }
+
+    // game.exe+1A2B3C
+    let (name, offset) = split_offset(s)?;
+    Ok(PointerBase::Module {
+        name: name.to_string(),
+        offset,
+    })
+}
+
+fn parse_symbol(module: &str, s: &str) -> Result<PointerBase, ParseError> {
+    let (symbol, offset) = split_offset(s.trim())?;
+
+    if module.is_empty() {
+        return Err(ParseError::MissingName);
+    }
+
+    Ok(PointerBase::Symbol {
+        module: module.to_string(),
+        symbol: symbol.to_string(),
+        offset,
+    })
+}
+
+/// Splits a name from the offset that follows it. Only a '+' or '-' after the extension starts the
+/// offset, so names like `my-game.exe` can be written without quotes.
+fn split_offset(s: &str) -> Result<(&str, isize), ParseError> {
+    let extension = s.rfind('.').unwrap_or(0);
+    let (name, offset) = match s[extension..].find(['+', '-']) {
+        Some(i) => (s[..extension + i].trim(), &s[extension + i..]),
+        None => (s, ""),
+    };
+
+    if name.is_empty() {
+        return Err(ParseError::MissingName);
+    }
+
+    let offset = match offset {
+        "" => 0,
+        offset => parse_offset(offset).ok_or_else(|| ParseError::InvalidOffset(offset.into()))?,
+    };
+
+    Ok((name, offset))
+}
+
+/// Parses a signed hexadecimal offset such as `+0x10`, `-8` or `1A`
+fn parse_offset(s: &str) -> Option<isize> {
+    let s = s.trim();
+
+    let (negative, digits) = match s.as_bytes().first()? {
+        b'+' => (false, &s[1..]),
+        b'-' => (true, &s[1..]),
+        _ => (false, s),
+    };
+
+    let value = isize::try_from(parse_hex(digits.trim())?).ok()?;
+
+    Some(if negative { -value } else { value })
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    usize::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{mock::MockProcess, Protection};
+
+    fn module(name: &str, offset: isize) -> PointerBase {
+        PointerBase::Module {
+            name: name.to_string(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let cases = [
+            (
+                "\"game.exe\"+0x1A2B3C",
+                module("game.exe", 0x1A2B3C),
+                vec![],
+            ),
+            (
+                "game.exe+1A2B3C -> 10 -> -8",
+                module("game.exe", 0x1A2B3C),
+                vec![0x10, -8],
+            ),
+            ("my-game.exe + 0x10", module("my-game.exe", 0x10), vec![]),
+            ("libc.so.6-20", module("libc.so.6", -0x20), vec![]),
+            ("\"my+game\"", module("my+game", 0), vec![]),
+            ("game.exe", module("game.exe", 0), vec![]),
+            (
+                "0x7FF6A000 -> +0x48",
+                PointerBase::Absolute(RemoteAddress::new(0x7FF6A000)),
+                vec![0x48],
+            ),
+            (
+                "libfoo.so!render_text+0x10",
+                PointerBase::Symbol {
+                    module: "libfoo.so".to_string(),
+                    symbol: "render_text".to_string(),
+                    offset: 0x10,
+                },
+                vec![],
+            ),
+            (
+                "\"lib-foo.so\" ! puts@@GLIBC_2.2.5 -> 0",
+                PointerBase::Symbol {
+                    module: "lib-foo.so".to_string(),
+                    symbol: "puts@@GLIBC_2.2.5".to_string(),
+                    offset: 0,
+                },
+                vec![0],
+            ),
+        ];
+
+        for (text, base, offsets) in cases {
+            let path = text.parse::<PointerPath>().unwrap();
+            assert_eq!(path, PointerPath { base, offsets }, "{text}");
+
+            // Display writes a form that parses back to the same path
+            assert_eq!(path.to_string().parse::<PointerPath>().unwrap(), path);
+        }
+    }
+
+    #[test]
+    fn display() {
+        let path = "game.exe+1A2B3C -> 10 -> -8 -> 0"
+            .parse::<PointerPath>()
+            .unwrap();
+        assert_eq!(
+            path.to_string(),
+            "\"game.exe\"+0x1A2B3C -> +0x10 -> -0x8 -> +0x0"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("", ParseError::Empty),
+            ("\"game.exe+10", ParseError::UnterminatedQuote),
+            ("game.exe+zz", ParseError::InvalidOffset("+zz".into())),
+            ("libc.so.6-", ParseError::InvalidOffset("-".into())),
+            ("\"game.exe\"+zz", ParseError::InvalidOffset("+zz".into())),
+            ("game.exe -> 0x", ParseError::InvalidOffset("0x".into())),
+            (
+                "libfoo.so!render_text+q",
+                ParseError::InvalidOffset("+q".into()),
+            ),
+            ("+10", ParseError::MissingName),
+            ("!render_text", ParseError::MissingName),
+            ("libfoo.so!+10", ParseError::MissingName),
+        ];
+
+        for (text, error) in cases {
+            assert_eq!(text.parse::<PointerPath>(), Err(error), "{text}");
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let mut process = MockProcess::new();
+        process.set_arch(crate::memory::ProcessArchitecture::X64);
+        process.map_zeroed(RemoteAddress::new(0x400000), 0x1000, Protection::READ_WRITE);
+        process.map_zeroed(RemoteAddress::new(0x800000), 0x1000, Protection::READ_WRITE);
+        process.add_module("Game.exe", RemoteAddress::new(0x400000), 0x1000);
+
+        process
+            .write_ptr(RemoteAddress::new(0x400100), RemoteAddress::new(0x800000))
+            .unwrap();
+        process
+            .write_ptr(RemoteAddress::new(0x800010), RemoteAddress::new(0x800200))
+            .unwrap();
+
+        let resolve = |text: &str| text.parse::<PointerPath>().unwrap().resolve(&process);
+
+        assert_eq!(
+            resolve("game.exe+100").unwrap(),
+            RemoteAddress::new(0x400100)
+        );
+        assert_eq!(
+            resolve("game.exe+100 -> 10 -> -8").unwrap(),
+            RemoteAddress::new(0x8001F8)
+        );
+        assert_eq!(
+            resolve("0x400100 -> 10").unwrap(),
+            RemoteAddress::new(0x800010)
+        );
+
+        assert!(matches!(
+            resolve("other.exe+100"),
+            Err(ResolveError::ModuleNotFound(name)) if name == "other.exe"
+        ));
+        // The second hop reads a null pointer at 0x800008, which the third one follows
+        assert!(matches!(
+            resolve("game.exe+100 -> 8 -> 0 -> 0"),
+            Err(ResolveError::Read { hop: 3, address, .. }) if address.is_null()
+        ));
+        assert!(matches!(
+            resolve("0x0 -> -1"),
+            Err(ResolveError::Read { hop: 1, .. })
+        ));
+        assert!(matches!(
+            resolve("game.exe-500000"),
+            Err(ResolveError::Overflow { hop: 0, .. })
+        ));
+
+        // The module is not a PE image, so it has no exports to look symbols up in
+        assert!(matches!(
+            resolve("game.exe!render_text"),
+            Err(ResolveError::Symbols(_))
+        ));
+        assert!(matches!(
+            resolve("other.exe!render_text"),
+            Err(ResolveError::SymbolNotFound { module, symbol })
+                if module == "other.exe" && symbol == "render_text"
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+use std::{cell::OnceCell, ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+
+use windows::Win32::{
+    Foundation::{CloseHandle, BOOL, ERROR_NOACCESS, ERROR_PARTIAL_COPY, HANDLE, HMODULE},
+    System::{
+        Diagnostics::{
+            Debug::{ReadProcessMemory, WriteProcessMemory},
+            ToolHelp::{
+                CreateToolhelp32Snapshot, Module32NextW, Process32NextW, MODULEENTRY32W,
+                PROCESSENTRY32W, TH32CS_SNAPMODULE, TH32CS_SNAPPROCESS,
+            },
+        },
+        Memory::{
+            VirtualQueryEx, MEMORY_BASIC_INFORMATION, MEM_COMMIT, MEM_IMAGE, MEM_MAPPED,
+            MEM_RESERVE, PAGE_EXECUTE, PAGE_EXECUTE_READ, PAGE_EXECUTE_READWRITE,
+            PAGE_EXECUTE_WRITECOPY, PAGE_GUARD, PAGE_PROTECTION_FLAGS, PAGE_READONLY,
+            PAGE_READWRITE, PAGE_WRITECOPY,
+        },
+        ProcessStatus::GetMappedFileNameW,
+        Threading::{
+            IsWow64Process, OpenProcess, QueryFullProcessImageNameW, PROCESS_ALL_ACCESS,
+            PROCESS_NAME_WIN32, PROCESS_QUERY_INFORMATION,
+        },
+    },
+    UI::WindowsAndMessaging::HICON,
+};
+
+use crate::{pe::PeImage, util};
+
+use super::{
+    Error, FileInfo, MemoryRegion, MemorySource, ModuleInfo, ProcessArchitecture, Protection,
+    RegionKind, RegionState, RemoteAddress, Result,
+};
+
+pub struct ProcessSnapshot {
+    handle: HANDLE,
+}
+
+impl ProcessSnapshot {
+    /// Creates an iterable snapshot of the currently running processes
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            handle: unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0)? },
+        })
+    }
+}
+
+impl Iterator for ProcessSnapshot {
+    type Item = ProcessEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = PROCESSENTRY32W {
+            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        unsafe { Process32NextW(self.handle, &mut entry) }.ok()?;
+
+        Some(ProcessEntry { entry })
+    }
+}
+
+impl Drop for ProcessSnapshot {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+pub struct ProcessEntry {
+    entry: PROCESSENTRY32W,
+}
+
+impl ProcessEntry {
+    /// Returns the process id of the process
+    pub fn process_id(&self) -> u32 {
+        self.entry.th32ProcessID
+    }
+
+    /// Returns the process id of the parent process
+    pub fn parent_process_id(&self) -> u32 {
+        self.entry.th32ParentProcessID
+    }
+
+    /// Returns the name of the process, or an error if it cannot be parsed into UTF-8
+    pub fn process_name(&self) -> Result<String> {
+        let str = self
+            .entry
+            .szExeFile
+            .iter()
+            .cloned()
+            .take_while(|c| *c != 0)
+            .collect::<Vec<u16>>();
+
+        String::from_utf16(&str).map_err(|_| Error::InvalidString)
+
+        //OsString::from_wide(&self.entry.szExeFile.iter().take_while(|c| c != 0).collect::<Vec<u16>>()).to_str().ok_or(Error::InvalidUtf8).map(str::to_string)
+    }
+
+    pub fn executable_path_full(&self) -> Vec<u16> {
+        // let vec = Vec::with_capacity(1024);
+        // unsafe {
+        //     QueryFullProcessImageNameW(hproc, 0, vec.as_mut_ptr(), vec.capacity());
+        // }
+
+        todo!()
+    }
+
+    pub fn process_name_buf(&self) -> [u16; 260] {
+        self.entry.szExeFile
+    }
+
+    pub fn process_descriptions(&self) -> Result<Vec<()>> {
+        // See: https://stackoverflow.com/a/61711510
+
+        todo!()
+    }
+
+    pub fn modules(&self) -> Result<ModuleSnapshot> {
+        ModuleSnapshot::new(self.process_id())
+    }
+
+    pub fn open(&mut self) -> Result<Process> {
+        Process::open(self.process_id())
+    }
+}
+
+pub struct ModuleSnapshot {
+    handle: HANDLE,
+}
+
+impl ModuleSnapshot {
+    /// Creates an iterable snapshot of the currently running processes
+    pub fn new(process_id: u32) -> Result<Self> {
+        Ok(Self {
+            handle: unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE, process_id)? },
+        })
+    }
+}
+
+impl Iterator for ModuleSnapshot {
+    type Item = Module;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = MODULEENTRY32W {
+            dwSize: std::mem::size_of::<MODULEENTRY32W>() as u32,
+            ..Default::default()
+        };
+
+        unsafe { Module32NextW(self.handle, &mut entry) }.ok()?;
+
+        Some(Module { entry })
+    }
+}
+
+impl Drop for ModuleSnapshot {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.handle) };
+    }
+}
+
+pub struct Module {
+    entry: MODULEENTRY32W,
+}
+
+impl Module {
+    /// Returns the module id of the module
+    pub fn module_id(&self) -> u32 {
+        self.entry.th32ModuleID
+    }
+
+    /// Returns the name of the module, or an error if it cannot be parsed into UTF-8
+    pub fn module_name(&self) -> Result<String> {
+        let str = self
+            .entry
+            .szModule
+            .iter()
+            .cloned()
+            .take_while(|c| *c != 0)
+            .collect::<Vec<u16>>();
+
+        String::from_utf16(&str).map_err(|_| Error::InvalidString)
+    }
+
+    pub fn module_addr(&self) -> RemoteAddress {
+        RemoteAddress::new(self.entry.modBaseAddr as usize)
+    }
+
+    /// Returns the number of bytes the module occupies in memory
+    pub fn module_size(&self) -> usize {
+        self.entry.modBaseSize as usize
+    }
+
+    /// Returns the full path of the module file
+    pub fn module_path(&self) -> Result<PathBuf> {
+        let path = &self.entry.szExePath;
+        let len = path.iter().position(|c| *c == 0).unwrap_or(path.len());
+
+        Ok(PathBuf::from(OsString::from_wide(&path[..len])))
+    }
+
+    /// Returns the handle of the module, which is only valid inside the process it belongs to
+    pub fn module_handle(&self) -> HMODULE {
+        self.entry.hModule
+    }
+
+    /// Whether an address lies within the memory of the module
+    pub fn contains(&self, address: RemoteAddress) -> bool {
+        (self.module_addr()..self.module_addr() + self.module_size()).contains(&address)
+    }
+}
+
+pub struct Process {
+    process_id: u32,
+    process_handle: HANDLE,
+    arch: OnceCell<ProcessArchitecture>,
+}
+
+impl Process {
+    /// Opens the memory of the given process id
+    pub fn open(process_id: u32) -> Result<Process> {
+        let process_handle = unsafe { OpenProcess(PROCESS_ALL_ACCESS, false, process_id)? };
+
+        Ok(Process {
+            process_id,
+            process_handle,
+            arch: OnceCell::new(),
+        })
+    }
+
+    pub fn __tmp_open_ro(process_id: u32) -> Result<Process> {
+        let process_handle = unsafe { OpenProcess(PROCESS_QUERY_INFORMATION, false, process_id)? };
+
+        Ok(Process {
+            process_id,
+            process_handle,
+            arch: OnceCell::new(),
+        })
+    }
+
+    /// Returns the process id opened
+    pub fn process_id(&self) -> u32 {
+        self.process_id
+    }
+
+    /// Returns the module an address lies in, such as to write it as an offset from the module
+    pub fn module_for_address(&self, address: RemoteAddress) -> Result<Option<Module>> {
+        Ok(ModuleSnapshot::new(self.process_id)?.find(|module| module.contains(address)))
+    }
+
+    fn query_arch(&self) -> Result<ProcessArchitecture> {
+        let is_wow64 = {
+            let mut is_wow64 = BOOL(0);
+            util::check(|| unsafe {
+                IsWow64Process(self.process_handle, std::ptr::addr_of_mut!(is_wow64))
+            })??;
+            is_wow64
+        };
+
+        // WOW64 processes are 32-bit processes running on 64-bit Windows, any other process has
+        // the same architecture as the OS.
+        let arch = match is_wow64.as_bool() {
+            true => ProcessArchitecture::X86,
+            false => ProcessArchitecture::host(),
+        };
+
+        Ok(arch)
+    }
+
+    pub fn executable_path(&self) -> Result<windows::core::HSTRING> {
+        let mut len: u32 = 1024;
+        let mut buffer = vec![0; len as usize];
+
+        unsafe {
+            QueryFullProcessImageNameW(
+                self.process_handle,
+                PROCESS_NAME_WIN32,
+                windows::core::PWSTR(buffer.as_mut_ptr()),
+                std::ptr::addr_of_mut!(len),
+            )?
+        }
+
+        Ok(windows::core::HSTRING::from_wide(&buffer[0..len as usize])?)
+    }
+
+    /// Returns the path of the file mapped at an address, in NT device form (`\Device\...`)
+    fn mapped_file_name(&self, address: RemoteAddress) -> Option<PathBuf> {
+        let mut buffer = vec![0; 1024];
+
+        let len = unsafe { GetMappedFileNameW(self.process_handle, address.as_ptr(), &mut buffer) };
+
+        (len != 0).then(|| PathBuf::from(OsString::from_wide(&buffer[..len as usize])))
+    }
+
+    /// Extracts the image of the executable's icon that is closest to 16x16.
+    ///
+    /// The caller is responsible for freeing the HICON after use
+    pub fn icon(&self) -> Result<Option<HICON>> {
+        let path = PathBuf::from(self.executable_path()?.to_os_string());
+
+        let Some(icon) = PeImage::open(path)?.icon(16)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(icon.to_hicon()?))
+    }
+
+    /// Returns the version information of the executable in each of its languages
+    pub fn file_descriptions(&self) -> Result<Vec<FileInfo>> {
+        super::read_file_info(&PathBuf::from(self.executable_path()?.to_os_string()))
+    }
+}
+
+impl MemorySource for Process {
+    /// Reads the memory of the open process at an address into a buffer
+    fn read(&self, address: RemoteAddress, buffer: &mut [u8]) -> Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let mut bytes_read = 0;
+
+        // Any process that has a handle with PROCESS_VM_READ access can call the function.
+        let result = unsafe {
+            ReadProcessMemory(
+                self.process_handle,
+                address.as_ptr(),
+                buffer.as_mut_ptr() as *mut std::ffi::c_void,
+                buffer.len(),
+                Some(&mut bytes_read),
+            )
+        };
+
+        match result {
+            Ok(()) => Ok(bytes_read),
+            // The range runs into memory that is not readable, like on the other backends
+            Err(err)
+                if err.code() == ERROR_PARTIAL_COPY.to_hresult()
+                    || err.code() == ERROR_NOACCESS.to_hresult() =>
+            {
+                match bytes_read {
+                    0 => Err(Error::InvalidAddress(address)),
+                    bytes_read => Ok(bytes_read),
+                }
+            }
+            Err(err) => Err(Error::Windows(err)),
+        }
+    }
+
+    /// Writes the contents of a buffer to a given address within the memory of the open process and return the number of bytes written
+    fn write(&self, address: RemoteAddress, buffer: &[u8]) -> Result<usize> {
+        let mut bytes_written = 0;
+
+        unsafe {
+            WriteProcessMemory(
+                self.process_handle,
+                address.as_ptr(),
+                buffer.as_ptr() as *const std::ffi::c_void,
+                buffer.len(),
+                Some(&mut bytes_written),
+            )
+        }
+        .map(|_| bytes_written)
+        .map_err(|_| Error::Windows(windows::core::Error::from_win32()))
+    }
+
+    fn regions(&self) -> Result<Vec<MemoryRegion>> {
+        let mut regions = Vec::new();
+        let mut address = RemoteAddress::NULL;
+
+        loop {
+            let mut info = MEMORY_BASIC_INFORMATION::default();
+
+            // Fails once we pass the highest address of the user address space
+            let written = unsafe {
+                VirtualQueryEx(
+                    self.process_handle,
+                    Some(address.as_ptr()),
+                    &mut info,
+                    std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+                )
+            };
+            if written == 0 {
+                break;
+            }
+
+            let base = RemoteAddress::from(info.BaseAddress as *const std::ffi::c_void);
+
+            let state = match info.State {
+                MEM_COMMIT => RegionState::Committed,
+                MEM_RESERVE => RegionState::Reserved,
+                _ => RegionState::Free,
+            };
+
+            // Free regions have no type, and the file name would just be that of the next image
+            let kind = match (state, info.Type) {
+                (RegionState::Free, _) => RegionKind::Private,
+                (_, MEM_IMAGE) => RegionKind::Image(self.mapped_file_name(base)),
+                (_, MEM_MAPPED) => RegionKind::Mapped(self.mapped_file_name(base)),
+                _ => RegionKind::Private,
+            };
+
+            regions.push(MemoryRegion {
+                base,
+                size: info.RegionSize,
+                protection: protection_from_flags(info.Protect),
+                state,
+                kind,
+            });
+
+            match base.checked_add(info.RegionSize) {
+                Some(next) if next > address => address = next,
+                _ => break,
+            }
+        }
+
+        Ok(regions)
+    }
+
+    fn modules(&self) -> Result<Vec<ModuleInfo>> {
+        ModuleSnapshot::new(self.process_id)?
+            .map(|module| {
+                Ok(ModuleInfo {
+                    name: module.module_name()?,
+                    base: module.module_addr(),
+                    size: module.module_size(),
+                })
+            })
+            .collect()
+    }
+
+    fn arch(&self) -> Result<ProcessArchitecture> {
+        if let Some(arch) = self.arch.get() {
+            return Ok(*arch);
+        }
+
+        let arch = self.query_arch()?;
+        Ok(*self.arch.get_or_init(|| arch))
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.process_handle) };
+    }
+}
+
+fn protection_from_flags(flags: PAGE_PROTECTION_FLAGS) -> Protection {
+    // Touching a guard page raises an exception in the target, so treat it as inaccessible
+    if flags.0 & PAGE_GUARD.0 != 0 {
+        return Protection::NONE;
+    }
+
+    // The upper bits are modifiers such as PAGE_NOCACHE
+    match PAGE_PROTECTION_FLAGS(flags.0 & 0xff) {
+        PAGE_READONLY => Protection::READ,
+        PAGE_READWRITE | PAGE_WRITECOPY => Protection::READ_WRITE,
+        PAGE_EXECUTE => Protection {
+            execute: true,
+            ..Protection::NONE
+        },
+        PAGE_EXECUTE_READ => Protection::READ_EXECUTE,
+        PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY => Protection::READ_WRITE_EXECUTE,
+        _ => Protection::NONE,
+    }
+}
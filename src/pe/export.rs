@@ -0,0 +1,150 @@
+use super::{directory, ffi, read, PeImage, Result};
+
+/// Where an exported function is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// The relative virtual address of the function in this image
+    Rva(u32),
+    /// The function is in another module, named like `KERNELBASE.GetProcAddress`, or
+    /// `KERNELBASE.#42` for an ordinal
+    Forwarder(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    /// The name of the function, unless it is only exported by ordinal
+    pub name: Option<String>,
+    pub ordinal: u16,
+    pub target: ExportTarget,
+}
+
+impl PeImage {
+    /// Returns the functions the image exports, in the order of their ordinals
+    pub fn exports(&self) -> Result<Vec<Export>> {
+        let Some(export_directory) = self.directory(directory::EXPORT) else {
+            return Ok(Vec::new());
+        };
+        let header = read::<ffi::ExportDirectory>(
+            self.bytes_at(
+                export_directory.virtual_address,
+                std::mem::size_of::<ffi::ExportDirectory>(),
+            )?,
+            0,
+        )?;
+
+        let functions = self.u32_table(header.address_of_functions, header.number_of_functions)?;
+        let names = self.u32_table(header.address_of_names, header.number_of_names)?;
+        let name_ordinals = self.bytes_at(
+            header.address_of_name_ordinals,
+            header.number_of_names as usize * 2,
+        )?;
+
+        // The names are sorted alphabetically, and each refers to a function by its index
+        let mut function_names = vec![None; functions.len()];
+        for (&name, ordinal) in names.iter().zip(name_ordinals.chunks_exact(2)) {
+            let index = u16::from_le_bytes([ordinal[0], ordinal[1]]) as usize;
+
+            if let Some(function_name) = function_names.get_mut(index) {
+                *function_name = Some(self.string_at(name)?);
+            }
+        }
+
+        let mut exports = Vec::new();
+        for (index, (&rva, name)) in functions.iter().zip(function_names).enumerate() {
+            // Gaps in the ordinals are left empty
+            if rva == 0 {
+                continue;
+            }
+
+            // Forwarders point to a string inside the export directory instead of code
+            let target =
+                if rva.wrapping_sub(export_directory.virtual_address) < export_directory.size {
+                    ExportTarget::Forwarder(self.string_at(rva)?)
+                } else {
+                    ExportTarget::Rva(rva)
+                };
+
+            exports.push(Export {
+                name,
+                ordinal: header.base.wrapping_add(index as u32) as u16,
+                target,
+            });
+        }
+
+        Ok(exports)
+    }
+
+    /// Returns an export by its name, which is case sensitive like `GetProcAddress`
+    pub fn export(&self, name: &str) -> Result<Option<Export>> {
+        Ok(self
+            .exports()?
+            .into_iter()
+            .find(|export| export.name.as_deref() == Some(name)))
+    }
+
+    /// Returns an export by its ordinal
+    pub fn export_by_ordinal(&self, ordinal: u16) -> Result<Option<Export>> {
+        Ok(self
+            .exports()?
+            .into_iter()
+            .find(|export| export.ordinal == ordinal))
+    }
+
+    fn u32_table(&self, rva: u32, count: u32) -> Result<Vec<u32>> {
+        Ok(self
+            .bytes_at(rva, count as usize * 4)?
+            .chunks_exact(4)
+            .map(|value| u32::from_le_bytes(value.try_into().unwrap()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::{
+        tests::{IMPORTS_DLL, VERSION_DLL},
+        Layout,
+    };
+
+    #[test]
+    fn fixture() {
+        let image = PeImage::parse(IMPORTS_DLL.to_vec(), Layout::File).unwrap();
+        let exports = image.exports().unwrap();
+
+        let names = exports
+            .iter()
+            .map(|export| (export.name.as_deref(), export.ordinal))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [(None, 5), (Some("Forwarded"), 6), (Some("render_text"), 7)]
+        );
+
+        let render_text = image.export("render_text").unwrap().unwrap();
+        let ExportTarget::Rva(rva) = render_text.target else {
+            panic!("render_text is not in the image");
+        };
+        assert_eq!(image.section_at(rva).unwrap().name, ".text");
+        // call qword ptr [rip + ...]
+        assert_eq!(image.bytes_at(rva, 2).unwrap(), [0xFF, 0x15]);
+
+        assert_eq!(
+            image.export("Forwarded").unwrap().unwrap().target,
+            ExportTarget::Forwarder("target.Target".to_string())
+        );
+        assert_eq!(image.export("forwarded").unwrap(), None);
+
+        // by_ordinal follows the two calls and the jump of render_text
+        let by_ordinal = image.export_by_ordinal(5).unwrap().unwrap();
+        assert_eq!(by_ordinal.name, None);
+        assert_eq!(by_ordinal.target, ExportTarget::Rva(rva + 18));
+        assert_eq!(image.export_by_ordinal(4).unwrap(), None);
+    }
+
+    #[test]
+    fn no_exports() {
+        let image = PeImage::parse(VERSION_DLL.to_vec(), Layout::File).unwrap();
+        assert_eq!(image.exports().unwrap(), []);
+    }
+}
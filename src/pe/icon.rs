@@ -0,0 +1,250 @@
+use std::cmp::Reverse;
+
+use super::{
+    ffi, png, read,
+    resource::{ResourceId, RT_GROUP_ICON, RT_ICON},
+    PeError, PeImage, Result,
+};
+
+/// The sizes icons usually come in
+pub const ICON_SIZES: [u32; 4] = [16, 32, 48, 256];
+
+/// One of the images of an icon, as listed by its group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconEntry {
+    pub width: u32,
+    pub height: u32,
+    pub bit_count: u16,
+    /// The id of the `RT_ICON` resource holding the image
+    pub id: u16,
+}
+
+/// A decoded icon image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconImage {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels as RGBA, row by row from the top, without premultiplied alpha
+    pub rgba: Vec<u8>,
+}
+
+impl IconImage {
+    /// Decodes the data of an `RT_ICON` resource, which is either a PNG or a BMP without its file
+    /// header
+    pub fn decode(data: &[u8]) -> Result<IconImage> {
+        if data.starts_with(&png::SIGNATURE) {
+            png::decode(data)
+        } else {
+            decode_bmp(data)
+        }
+    }
+}
+
+impl PeImage {
+    /// Returns the images of the main icon, which is the first one in the resources and the one
+    /// Explorer shows
+    pub fn icon_entries(&self) -> Result<Vec<IconEntry>> {
+        let Some(group) = self.resources(RT_GROUP_ICON)?.into_iter().next() else {
+            return Ok(Vec::new());
+        };
+
+        let header = read::<ffi::IconGroupHeader>(group.data, 0)?;
+        if header.kind != 1 {
+            return Err(PeError::InvalidIcon);
+        }
+
+        (0..header.count as usize)
+            .map(|i| {
+                let entry = read::<ffi::IconGroupEntry>(
+                    group.data,
+                    std::mem::size_of::<ffi::IconGroupHeader>()
+                        + i * std::mem::size_of::<ffi::IconGroupEntry>(),
+                )?;
+
+                Ok(IconEntry {
+                    width: icon_size(entry.width),
+                    height: icon_size(entry.height),
+                    bit_count: entry.bit_count,
+                    id: entry.id,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the image of the main icon that is closest to `size` pixels, if there is an icon.
+    ///
+    /// The image is not scaled, so it may be larger or smaller than asked for.
+    pub fn icon(&self, size: u32) -> Result<Option<IconImage>> {
+        let Some(entry) = best_icon(&self.icon_entries()?, size) else {
+            return Ok(None);
+        };
+        let resource = self
+            .resource(RT_ICON, &ResourceId::Id(entry.id))?
+            .ok_or(PeError::InvalidIcon)?;
+
+        IconImage::decode(resource.data).map(Some)
+    }
+}
+
+/// Sizes are stored in a byte, where 0 means 256
+fn icon_size(size: u8) -> u32 {
+    match size {
+        0 => 256,
+        size => size as u32,
+    }
+}
+
+/// Picks the image that looks best at `size` pixels, which is the one of that size with the most
+/// colors. Otherwise, scaling a larger image down looks better than scaling a smaller one up.
+pub fn best_icon(entries: &[IconEntry], size: u32) -> Option<IconEntry> {
+    entries.iter().copied().min_by_key(|entry| {
+        let entry_size = entry.width.max(entry.height);
+        (
+            entry_size < size,
+            entry_size.abs_diff(size),
+            Reverse(entry.bit_count),
+        )
+    })
+}
+
+/// Decodes a `BITMAPINFOHEADER` followed by the color table, the colors and the transparency mask,
+/// both of which are stored from the bottom row up
+fn decode_bmp(data: &[u8]) -> Result<IconImage> {
+    let header = read::<ffi::BitmapInfoHeader>(data, 0).map_err(|_| PeError::InvalidIcon)?;
+
+    // The height covers both the colors and the mask
+    let width = header.width.unsigned_abs();
+    let height = header.height.unsigned_abs() / 2;
+    let bit_count = header.bit_count as usize;
+
+    // BI_BITFIELDS is only seen with the default masks, so it is read like BI_RGB
+    if !matches!(header.compression, 0 | 3) || !matches!(bit_count, 1 | 4 | 8 | 16 | 24 | 32) {
+        return Err(PeError::UnsupportedIcon);
+    }
+    if width == 0 || height == 0 || width > 1024 || height > 1024 {
+        return Err(PeError::InvalidIcon);
+    }
+    let (width_px, height_px) = (width as usize, height as usize);
+
+    let mut offset = header.size as usize;
+    if header.compression == 3 {
+        offset += 12;
+    }
+
+    let palette = if bit_count <= 8 {
+        let len = match header.clr_used {
+            0 => 1 << bit_count,
+            len => (len as usize).min(1 << bit_count),
+        };
+        let palette = data
+            .get(offset..offset + len * 4)
+            .ok_or(PeError::InvalidIcon)?;
+        offset += len * 4;
+        palette
+    } else {
+        &[]
+    };
+
+    let stride = (width_px * bit_count).div_ceil(32) * 4;
+    let mask_stride = width_px.div_ceil(32) * 4;
+    let colors = data
+        .get(offset..offset + stride * height_px)
+        .ok_or(PeError::InvalidIcon)?;
+    // Some 32-bit images leave out the mask, since their alpha makes it unnecessary
+    let mask = data.get(offset + stride * height_px..offset + (stride + mask_stride) * height_px);
+
+    let mut rgba = Vec::with_capacity(width_px * height_px * 4);
+    for y in 0..height_px {
+        let row = &colors[(height_px - 1 - y) * stride..][..stride];
+
+        for x in 0..width_px {
+            let [b, g, r, a] = match bit_count {
+                32 => [row[x * 4], row[x * 4 + 1], row[x * 4 + 2], row[x * 4 + 3]],
+                24 => [row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 0xFF],
+                16 => {
+                    // 5 bits each of red, green and blue
+                    let pixel = u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]);
+                    let scale = |bits: u16| ((bits & 0x1F) * 255 / 31) as u8;
+                    [scale(pixel), scale(pixel >> 5), scale(pixel >> 10), 0xFF]
+                }
+                _ => {
+                    let bit = x * bit_count;
+                    let index = (row[bit / 8] as usize >> (8 - bit_count - bit % 8))
+                        & ((1 << bit_count) - 1);
+                    match palette.get(index * 4..index * 4 + 3) {
+                        Some(&[b, g, r]) => [b, g, r, 0xFF],
+                        _ => [0, 0, 0, 0xFF],
+                    }
+                }
+            };
+
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+
+    // Images with an alpha channel that is entirely zero predate it, and rely on the mask instead
+    let has_alpha = bit_count == 32 && rgba.chunks_exact(4).any(|pixel| pixel[3] != 0);
+    if !has_alpha {
+        let mask = mask.ok_or(PeError::InvalidIcon)?;
+
+        for y in 0..height_px {
+            let row = &mask[(height_px - 1 - y) * mask_stride..][..mask_stride];
+
+            for x in 0..width_px {
+                let transparent = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                rgba[(y * width_px + x) * 4 + 3] = if transparent { 0 } else { 0xFF };
+            }
+        }
+    }
+
+    Ok(IconImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+#[cfg(windows)]
+impl IconImage {
+    /// Creates an icon that can be drawn with the Windows API.
+    ///
+    /// The caller is responsible for freeing the HICON after use
+    pub fn to_hicon(
+        &self,
+    ) -> windows::core::Result<windows::Win32::UI::WindowsAndMessaging::HICON> {
+        use windows::Win32::{
+            Foundation::TRUE,
+            Graphics::Gdi::{CreateBitmap, DeleteObject},
+            UI::WindowsAndMessaging::{CreateIconIndirect, ICONINFO},
+        };
+
+        let (width, height) = (self.width as i32, self.height as i32);
+
+        let bgra = self
+            .rgba
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+            .collect::<Vec<u8>>();
+        // The alpha channel takes precedence, so the mask can be left empty. Its rows are aligned
+        // to 2 bytes.
+        let mask = vec![0u8; (self.width as usize).div_ceil(16) * 2 * self.height as usize];
+
+        unsafe {
+            let info = ICONINFO {
+                fIcon: TRUE,
+                xHotspot: 0,
+                yHotspot: 0,
+                hbmMask: CreateBitmap(width, height, 1, 1, Some(mask.as_ptr().cast())),
+                hbmColor: CreateBitmap(width, height, 1, 32, Some(bgra.as_ptr().cast())),
+            };
+
+            let icon = CreateIconIndirect(&info);
+
+            // The icon holds copies of the bitmaps
+            let _ = DeleteObject(info.hbmMask);
+            let _ = DeleteObject(info.hbmColor);
+
+            icon
+        }
+    }
+}
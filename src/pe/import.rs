@@ -0,0 +1,217 @@
+use super::{directory, ffi, read, PeError, PeImage, Result};
+use crate::memory::ProcessArchitecture;
+
+/// How an imported function is looked up in its module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportName {
+    /// By name, with a guess of the index of the name in the module's exports
+    Name {
+        hint: u16,
+        name: String,
+    },
+    Ordinal(u16),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFunction {
+    pub name: ImportName,
+    /// The relative virtual address of the slot in the import address table that the loader
+    /// writes the address of the function to
+    pub address: u32,
+}
+
+/// The functions the image imports from one module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub module: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+impl PeImage {
+    /// Returns the modules the image imports functions from, leaving out delay loaded ones
+    pub fn imports(&self) -> Result<Vec<Import>> {
+        // The loader ignores the size of the directory and so do we, since some linkers and
+        // packers leave it at zero
+        let Some(imports) = self
+            .directories
+            .get(directory::IMPORT)
+            .filter(|imports| imports.virtual_address != 0)
+        else {
+            return Ok(Vec::new());
+        };
+        let descriptors = self.bytes_from(imports.virtual_address)?;
+        let thunk_size = self.arch.pointer_size();
+
+        // The list ends with an empty descriptor, which has to be within the section
+        let descriptor_size = std::mem::size_of::<ffi::ImportDescriptor>();
+
+        let mut modules = Vec::new();
+        for offset in (0..).step_by(descriptor_size) {
+            let descriptor = read::<ffi::ImportDescriptor>(descriptors, offset)?;
+            if descriptor.name == 0 && descriptor.first_thunk == 0 {
+                break;
+            }
+
+            // Some linkers leave out the lookup table, in which case the names are only in the
+            // address table until the loader overwrites them
+            let lookup = match descriptor.original_first_thunk {
+                0 => descriptor.first_thunk,
+                rva => rva,
+            };
+            let thunks = self.bytes_from(lookup)?;
+
+            let mut functions = Vec::new();
+            for (i, thunk) in thunks.chunks_exact(thunk_size).enumerate() {
+                let (thunk, by_ordinal) = match self.arch {
+                    ProcessArchitecture::X86 => {
+                        let thunk = u32::from_le_bytes(thunk.try_into().unwrap());
+                        (thunk as u64, thunk & 0x8000_0000 != 0)
+                    }
+                    ProcessArchitecture::X64 => {
+                        let thunk = u64::from_le_bytes(thunk.try_into().unwrap());
+                        (thunk, thunk & 0x8000_0000_0000_0000 != 0)
+                    }
+                };
+                if thunk == 0 {
+                    break;
+                }
+
+                let name = if by_ordinal {
+                    ImportName::Ordinal(thunk as u16)
+                } else {
+                    // The rest of the bits hold the address of the hint followed by the name
+                    let rva = (thunk & 0x7FFF_FFFF) as u32;
+                    ImportName::Name {
+                        hint: read::<u16>(self.bytes_at(rva, 2)?, 0)?,
+                        name: self.string_at(rva.checked_add(2).ok_or(PeError::OutOfBounds)?)?,
+                    }
+                };
+
+                let address = i
+                    .checked_mul(thunk_size)
+                    .and_then(|offset| u32::try_from(offset).ok())
+                    .and_then(|offset| descriptor.first_thunk.checked_add(offset))
+                    .ok_or(PeError::OutOfBounds)?;
+
+                functions.push(ImportedFunction { name, address });
+            }
+
+            modules.push(Import {
+                module: self.string_at(descriptor.name)?,
+                functions,
+            });
+        }
+
+        Ok(modules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use super::*;
+    use crate::pe::{tests::IMPORTS_DLL, Layout};
+
+    fn name(name: &str) -> ImportName {
+        ImportName::Name {
+            hint: 0,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn fixture() {
+        let image = PeImage::parse(IMPORTS_DLL.to_vec(), Layout::File).unwrap();
+        let imports = image.imports().unwrap();
+
+        let modules = imports
+            .iter()
+            .map(|i| i.module.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(modules, ["target.dll", "other.dll"]);
+
+        let target = imports[0]
+            .functions
+            .iter()
+            .map(|f| &f.name)
+            .collect::<Vec<_>>();
+        assert_eq!(target, [&ImportName::Ordinal(7), &name("Target")]);
+        let other = imports[1]
+            .functions
+            .iter()
+            .map(|f| &f.name)
+            .collect::<Vec<_>>();
+        assert_eq!(other, [&name("Other")]);
+
+        // The slots of a module follow each other in the import address table
+        let slots = &imports[0].functions;
+        assert_eq!(slots[1].address, slots[0].address + 8);
+        assert!(image.section_at(slots[0].address).is_some());
+    }
+
+    /// Returns where the first import descriptor is in the file
+    fn first_descriptor(image: &PeImage) -> usize {
+        let imports = image.directory(directory::IMPORT).unwrap();
+        image.rva_to_offset(imports.virtual_address).unwrap()
+    }
+
+    /// Returns where the import directory is in the data directories of the file
+    fn import_directory(data: &[u8]) -> usize {
+        let nt_headers = u32::from_le_bytes(data[0x3C..0x40].try_into().unwrap()) as usize;
+        nt_headers + 24 + 112 + directory::IMPORT * 8
+    }
+
+    #[test]
+    fn directory_size_is_ignored() {
+        let mut data = IMPORTS_DLL.to_vec();
+        let size = import_directory(&data) + 4;
+        data[size..size + 4].copy_from_slice(&0u32.to_le_bytes());
+
+        let image = PeImage::parse(data, Layout::File).unwrap();
+        let modules = image
+            .imports()
+            .unwrap()
+            .into_iter()
+            .map(|i| i.module)
+            .collect::<Vec<_>>();
+        assert_eq!(modules, ["target.dll", "other.dll"]);
+    }
+
+    #[test]
+    fn descriptors_are_bounded_by_the_section() {
+        let image = PeImage::parse(IMPORTS_DLL.to_vec(), Layout::File).unwrap();
+        let imports = image.directory(directory::IMPORT).unwrap();
+        let descriptors = image.bytes_from(imports.virtual_address).unwrap();
+        let first = read::<ffi::ImportDescriptor>(descriptors, 0).unwrap();
+        let section = image.section_at(imports.virtual_address).unwrap();
+        let end = section.raw_offset + section.raw_size;
+        assert_eq!(&IMPORTS_DLL[end as usize - 8..end as usize], [0; 8]);
+
+        // Move the list to the end of the section, where the first descriptor is cut off by data
+        // that would make it a valid one
+        let mut data = IMPORTS_DLL.to_vec();
+        let rva = import_directory(&data);
+        let list = section.virtual_address + section.raw_size - 8;
+        data[rva..rva + 4].copy_from_slice(&list.to_le_bytes());
+        for field in [0, first.name, first.first_thunk] {
+            data.extend(field.to_le_bytes());
+        }
+        data.extend([0; 20]);
+
+        let image = PeImage::parse(data, Layout::File).unwrap();
+        assert!(matches!(image.imports(), Err(PeError::OutOfBounds)));
+    }
+
+    #[test]
+    fn addresses_do_not_overflow() {
+        let image = PeImage::parse(IMPORTS_DLL.to_vec(), Layout::File).unwrap();
+        let first_thunk = first_descriptor(&image) + offset_of!(ffi::ImportDescriptor, first_thunk);
+
+        let mut data = IMPORTS_DLL.to_vec();
+        data[first_thunk..first_thunk + 4].copy_from_slice(&(u32::MAX - 4).to_le_bytes());
+
+        let image = PeImage::parse(data, Layout::File).unwrap();
+        assert!(matches!(image.imports(), Err(PeError::OutOfBounds)));
+    }
+}
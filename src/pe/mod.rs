@@ -0,0 +1,496 @@
+#![allow(dead_code)]
+
+use std::{ops::Range, path::Path};
+
+use bytemuck::Pod;
+use thiserror::Error;
+
+use crate::memory::{self, MemorySource, ProcessArchitecture, RemoteAddress};
+
+pub mod export;
+pub mod icon;
+pub mod import;
+mod png;
+pub mod resource;
+pub mod version;
+
+#[derive(Error, Debug)]
+pub enum PeError {
+    #[error("An I/O error occurred")]
+    Io(#[from] std::io::Error),
+    #[error("The file is not a PE image")]
+    NotPe,
+    #[error("The optional header has the unknown magic {0:#x}")]
+    UnknownMagic(u16),
+    #[error("The image is truncated or points outside of itself")]
+    OutOfBounds,
+    #[error("The resource directory is malformed")]
+    InvalidResource,
+    #[error("The version information is malformed")]
+    InvalidVersion,
+    #[error("The icon is malformed")]
+    InvalidIcon,
+    #[error("The icon is stored in an unsupported format")]
+    UnsupportedIcon,
+}
+
+pub type Result<T> = std::result::Result<T, PeError>;
+
+const PAGE_SIZE: usize = 0x1000;
+const MAX_IMAGE_SIZE: usize = 0x4000_0000;
+
+/// The indices of the data directories in the optional header
+pub mod directory {
+    pub const EXPORT: usize = 0;
+    pub const IMPORT: usize = 1;
+    pub const RESOURCE: usize = 2;
+}
+
+/// How the image is laid out in the buffer it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// As it is stored on disk, where sections start at their raw offsets
+    File,
+    /// As it is loaded into a process, where sections start at their relative virtual addresses
+    Mapped,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataDirectory {
+    pub virtual_address: u32,
+    pub size: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+    pub characteristics: u32,
+}
+
+impl Section {
+    /// Returns the relative virtual addresses the section is loaded at
+    pub fn range(&self) -> Range<u32> {
+        self.virtual_address..self.virtual_address.saturating_add(self.virtual_size)
+    }
+}
+
+/// A Portable Executable, such as an exe or a dll, parsed without the help of the OS
+pub struct PeImage {
+    data: Vec<u8>,
+    layout: Layout,
+    pub machine: u16,
+    pub arch: ProcessArchitecture,
+    pub image_base: u64,
+    /// The relative virtual address of the entry point, or 0 if there is none
+    pub entry_point: u32,
+    /// The number of bytes the image takes up once loaded
+    pub size_of_image: u32,
+    size_of_headers: u32,
+    directories: Vec<DataDirectory>,
+    sections: Vec<Section>,
+}
+
+impl PeImage {
+    /// Reads and parses an image from disk
+    pub fn open(path: impl AsRef<Path>) -> Result<PeImage> {
+        PeImage::parse(std::fs::read(path)?, Layout::File)
+    }
+
+    pub fn parse(data: Vec<u8>, layout: Layout) -> Result<PeImage> {
+        if data.get(..2) != Some(b"MZ") {
+            return Err(PeError::NotPe);
+        }
+
+        let nt_headers = read::<u32>(&data, 0x3C)? as usize;
+        if data.get(nt_headers..nt_headers + 4) != Some(b"PE\0\0") {
+            return Err(PeError::NotPe);
+        }
+
+        let file_header = read::<ffi::FileHeader>(&data, nt_headers + 4)?;
+        let optional_header = nt_headers + 4 + std::mem::size_of::<ffi::FileHeader>();
+
+        let magic = read::<u16>(&data, optional_header)?;
+        let (arch, image_base, directories_offset) = match magic {
+            0x10B => (
+                ProcessArchitecture::X86,
+                read::<u32>(&data, optional_header + 28)? as u64,
+                optional_header + 92,
+            ),
+            0x20B => (
+                ProcessArchitecture::X64,
+                read::<u64>(&data, optional_header + 24)?,
+                optional_header + 108,
+            ),
+            magic => return Err(PeError::UnknownMagic(magic)),
+        };
+
+        // The count is not always honest, but there are never more than 16 directories
+        let directory_count = read::<u32>(&data, directories_offset)?.min(16) as usize;
+        let directories = (0..directory_count)
+            .map(|i| {
+                let directory = read::<ffi::DataDirectory>(&data, directories_offset + 4 + i * 8)?;
+                Ok(DataDirectory {
+                    virtual_address: directory.virtual_address,
+                    size: directory.size,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let section_table = optional_header + file_header.size_of_optional_header as usize;
+        let sections = (0..file_header.number_of_sections as usize)
+            .map(|i| {
+                let header = read::<ffi::SectionHeader>(
+                    &data,
+                    section_table + i * std::mem::size_of::<ffi::SectionHeader>(),
+                )?;
+                let name_len = header.name.iter().position(|&b| b == 0).unwrap_or(8);
+
+                Ok(Section {
+                    name: String::from_utf8_lossy(&header.name[..name_len]).into_owned(),
+                    virtual_address: header.virtual_address,
+                    virtual_size: header.virtual_size,
+                    raw_offset: header.pointer_to_raw_data,
+                    raw_size: header.size_of_raw_data,
+                    characteristics: header.characteristics,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(PeImage {
+            machine: file_header.machine,
+            arch,
+            image_base,
+            entry_point: read::<u32>(&data, optional_header + 16)?,
+            size_of_image: read::<u32>(&data, optional_header + 56)?,
+            size_of_headers: read::<u32>(&data, optional_header + 60)?,
+            directories,
+            sections,
+            data,
+            layout,
+        })
+    }
+
+    /// Reads an image that is loaded into an address space, such as a module of a process.
+    ///
+    /// Sections that cannot be read, such as those that are not committed, are left zeroed.
+    pub fn read<S: MemorySource>(source: &S, base: RemoteAddress) -> memory::Result<PeImage> {
+        let mut headers = vec![0; PAGE_SIZE];
+        let len = source.read(base, &mut headers)?;
+        headers.truncate(len);
+
+        let image = PeImage::parse(headers, Layout::Mapped)?;
+        // Anything larger is not an image someone loaded on purpose
+        if image.size_of_image as usize > MAX_IMAGE_SIZE {
+            return Err(PeError::OutOfBounds.into());
+        }
+
+        let mut data = image.data;
+        data.resize(image.size_of_image as usize, 0);
+
+        for section in &image.sections {
+            let start = (section.virtual_address as usize).min(data.len());
+            let end = (start + section.virtual_size.max(section.raw_size) as usize).min(data.len());
+
+            let _ = source.read(base + start, &mut data[start..end]);
+        }
+
+        Ok(PeImage::parse(data, Layout::Mapped)?)
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Returns a data directory, unless the image does not have it
+    pub fn directory(&self, index: usize) -> Option<DataDirectory> {
+        self.directories
+            .get(index)
+            .copied()
+            .filter(|directory| directory.virtual_address != 0 && directory.size != 0)
+    }
+
+    /// Returns the section that contains a relative virtual address
+    pub fn section_at(&self, rva: u32) -> Option<&Section> {
+        self.sections.iter().find(|section| {
+            let size = section.virtual_size.max(section.raw_size);
+            rva.wrapping_sub(section.virtual_address) < size
+        })
+    }
+
+    /// Returns where a relative virtual address is in the buffer
+    pub fn rva_to_offset(&self, rva: u32) -> Option<usize> {
+        if self.layout == Layout::Mapped || rva < self.size_of_headers {
+            return Some(rva as usize);
+        }
+
+        let section = self.section_at(rva)?;
+        let offset = rva - section.virtual_address;
+
+        // Whatever is past the raw data is zero filled when loaded, so it is not in the file
+        if offset >= section.raw_size {
+            return None;
+        }
+
+        section
+            .raw_offset
+            .checked_add(offset)
+            .map(|offset| offset as usize)
+    }
+
+    /// Returns `len` bytes starting at a relative virtual address
+    pub fn bytes_at(&self, rva: u32, len: usize) -> Result<&[u8]> {
+        let offset = self.rva_to_offset(rva).ok_or(PeError::OutOfBounds)?;
+
+        self.data
+            .get(offset..offset.checked_add(len).ok_or(PeError::OutOfBounds)?)
+            .ok_or(PeError::OutOfBounds)
+    }
+
+    /// Returns the bytes from a relative virtual address to the end of what is stored of its
+    /// section
+    pub fn bytes_from(&self, rva: u32) -> Result<&[u8]> {
+        let offset = self.rva_to_offset(rva).ok_or(PeError::OutOfBounds)?;
+
+        let end = match (self.layout, self.section_at(rva)) {
+            (Layout::File, Some(section)) => section
+                .raw_offset
+                .checked_add(section.raw_size)
+                .ok_or(PeError::OutOfBounds)? as usize,
+            _ => self.data.len(),
+        };
+
+        self.data
+            .get(offset..end.min(self.data.len()))
+            .ok_or(PeError::OutOfBounds)
+    }
+
+    /// Reads a NUL-terminated string, such as the name of an export
+    pub fn string_at(&self, rva: u32) -> Result<String> {
+        let bytes = self.bytes_from(rva)?;
+        let len = memchr::memchr(0, bytes).ok_or(PeError::OutOfBounds)?;
+
+        Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+    }
+}
+
+/// Reads a plain value from anywhere in a buffer
+fn read<T: Pod>(data: &[u8], offset: usize) -> Result<T> {
+    data.get(offset..offset.saturating_add(std::mem::size_of::<T>()))
+        .map(bytemuck::pod_read_unaligned)
+        .ok_or(PeError::OutOfBounds)
+}
+
+mod ffi {
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct FileHeader {
+        pub machine: u16,
+        pub number_of_sections: u16,
+        pub time_date_stamp: u32,
+        pub pointer_to_symbol_table: u32,
+        pub number_of_symbols: u32,
+        pub size_of_optional_header: u16,
+        pub characteristics: u16,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct DataDirectory {
+        pub virtual_address: u32,
+        pub size: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct SectionHeader {
+        pub name: [u8; 8],
+        pub virtual_size: u32,
+        pub virtual_address: u32,
+        pub size_of_raw_data: u32,
+        pub pointer_to_raw_data: u32,
+        pub pointer_to_relocations: u32,
+        pub pointer_to_linenumbers: u32,
+        pub number_of_relocations: u16,
+        pub number_of_linenumbers: u16,
+        pub characteristics: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct ResourceDirectory {
+        pub characteristics: u32,
+        pub time_date_stamp: u32,
+        pub major_version: u16,
+        pub minor_version: u16,
+        pub number_of_named_entries: u16,
+        pub number_of_id_entries: u16,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct ResourceDirectoryEntry {
+        /// A string offset if the high bit is set, and an id otherwise
+        pub name: u32,
+        /// A directory offset if the high bit is set, and a data entry offset otherwise
+        pub offset_to_data: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct ResourceDataEntry {
+        /// Unlike the other offsets, this one is a relative virtual address
+        pub offset_to_data: u32,
+        pub size: u32,
+        pub code_page: u32,
+        pub reserved: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct FixedFileInfo {
+        pub signature: u32,
+        pub struct_version: u32,
+        pub file_version_ms: u32,
+        pub file_version_ls: u32,
+        pub product_version_ms: u32,
+        pub product_version_ls: u32,
+        pub file_flags_mask: u32,
+        pub file_flags: u32,
+        pub file_os: u32,
+        pub file_type: u32,
+        pub file_subtype: u32,
+        pub file_date_ms: u32,
+        pub file_date_ls: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct IconGroupHeader {
+        pub reserved: u16,
+        /// 1 for icons and 2 for cursors
+        pub kind: u16,
+        pub count: u16,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C, packed)]
+    pub struct IconGroupEntry {
+        pub width: u8,
+        pub height: u8,
+        pub color_count: u8,
+        pub reserved: u8,
+        pub planes: u16,
+        pub bit_count: u16,
+        pub bytes_in_resource: u32,
+        pub id: u16,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct BitmapInfoHeader {
+        pub size: u32,
+        pub width: i32,
+        pub height: i32,
+        pub planes: u16,
+        pub bit_count: u16,
+        pub compression: u32,
+        pub size_image: u32,
+        pub x_pels_per_meter: i32,
+        pub y_pels_per_meter: i32,
+        pub clr_used: u32,
+        pub clr_important: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct ExportDirectory {
+        pub characteristics: u32,
+        pub time_date_stamp: u32,
+        pub major_version: u16,
+        pub minor_version: u16,
+        pub name: u32,
+        pub base: u32,
+        pub number_of_functions: u32,
+        pub number_of_names: u32,
+        pub address_of_functions: u32,
+        pub address_of_names: u32,
+        pub address_of_name_ordinals: u32,
+    }
+
+    #[derive(Debug, Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+    #[repr(C)]
+    pub struct ImportDescriptor {
+        /// The lookup table, which still holds the names after the loader fills in the addresses
+        pub original_first_thunk: u32,
+        pub time_date_stamp: u32,
+        pub forwarder_chain: u32,
+        pub name: u32,
+        /// The import address table
+        pub first_thunk: u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DLL that has nothing but version information, built from version.rc and version.s with
+    /// llvm-rc, llvm-mc and lld-link
+    pub(super) const VERSION_DLL: &[u8] = include_bytes!("../../tests/fixtures/version.dll");
+    /// A DLL that imports from target.dll and other.dll, by name and by ordinal, and exports by
+    /// name, by ordinal and by forwarding, built from imports.s, target.s and other.s
+    pub(super) const IMPORTS_DLL: &[u8] = include_bytes!("../../tests/fixtures/imports.dll");
+
+    #[test]
+    fn headers() {
+        let image = PeImage::parse(VERSION_DLL.to_vec(), Layout::File).unwrap();
+        assert_eq!(image.arch, ProcessArchitecture::X64);
+
+        let names = image
+            .sections()
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".text", ".rsrc"]);
+        assert_eq!(image.section_at(0x2010).unwrap().name, ".rsrc");
+        assert!(image.section_at(0x4000).is_none());
+
+        // mov eax, 1; ret
+        assert_eq!(image.rva_to_offset(0x1000), Some(0x400));
+        assert_eq!(image.bytes_at(0x1000, 6).unwrap(), [0xB8, 1, 0, 0, 0, 0xC3]);
+        assert_eq!(image.bytes_from(0x1000).unwrap().len(), 0x200);
+
+        // Past the raw data of a section is only in memory, and past the file is nothing
+        assert_eq!(image.rva_to_offset(0x1200), None);
+        assert!(matches!(
+            image.bytes_at(0x2000, 0x401),
+            Err(PeError::OutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn raw_offsets_do_not_overflow() {
+        let mut image = PeImage::parse(VERSION_DLL.to_vec(), Layout::File).unwrap();
+
+        image.sections[1].raw_offset = u32::MAX - 0x10;
+        assert_eq!(image.rva_to_offset(0x2000), Some(u32::MAX as usize - 0x10));
+        assert_eq!(image.rva_to_offset(0x2020), None);
+        assert!(matches!(
+            image.bytes_from(0x2000),
+            Err(PeError::OutOfBounds)
+        ));
+        assert!(matches!(
+            image.bytes_at(0x2000, 1),
+            Err(PeError::OutOfBounds)
+        ));
+        assert!(matches!(image.string_at(0x2020), Err(PeError::OutOfBounds)));
+    }
+}
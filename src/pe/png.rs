@@ -0,0 +1,577 @@
+//! Just enough of PNG and DEFLATE to read the images of icons, which Windows stores as PNG since
+//! Vista for sizes of 256 pixels
+
+use super::{icon::IconImage, PeError, Result};
+
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Decodes a non-interlaced PNG image of any color type and bit depth
+pub fn decode(data: &[u8]) -> Result<IconImage> {
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    let mut rest = data.get(SIGNATURE.len()..).ok_or(PeError::InvalidIcon)?;
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let kind = &rest[4..8];
+        let chunk = rest.get(8..8 + len).ok_or(PeError::InvalidIcon)?;
+        // The CRC that follows is not checked, resources are not corrupted in transit
+        rest = &rest[(12 + len).min(rest.len())..];
+
+        match kind {
+            b"IHDR" => header = Some(chunk),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header
+        .filter(|header| header.len() == 13)
+        .ok_or(PeError::InvalidIcon)?;
+    let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let bit_depth = header[8] as usize;
+    let color_type = header[9];
+    let interlaced = header[12] != 0;
+
+    let channels = match color_type {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return Err(PeError::InvalidIcon),
+    };
+    if interlaced {
+        return Err(PeError::UnsupportedIcon);
+    }
+    if !matches!(bit_depth, 1 | 2 | 4 | 8 | 16) || width == 0 || height == 0 {
+        return Err(PeError::InvalidIcon);
+    }
+    if width > 1024 || height > 1024 {
+        return Err(PeError::UnsupportedIcon);
+    }
+    let (width_px, height_px) = (width as usize, height as usize);
+
+    let stride = (width_px * channels * bit_depth).div_ceil(8);
+    // Filters work on whole pixels, or bytes for bit depths below 8
+    let pixel_len = (channels * bit_depth).div_ceil(8);
+
+    // Each row starts with the byte that selects its filter
+    let mut pixels = inflate_zlib(&compressed, (stride + 1) * height_px)?;
+    if pixels.len() < (stride + 1) * height_px {
+        return Err(PeError::InvalidIcon);
+    }
+    unfilter(&mut pixels, stride, pixel_len, height_px)?;
+
+    let sample = |row: &[u8], i: usize| -> u16 {
+        match bit_depth {
+            8 => row[i] as u16,
+            16 => u16::from_be_bytes([row[i * 2], row[i * 2 + 1]]),
+            _ => {
+                let bit = i * bit_depth;
+                ((row[bit / 8] >> (8 - bit_depth - bit % 8)) & ((1 << bit_depth) - 1)) as u16
+            }
+        }
+    };
+    let scale = |value: u16| -> u8 {
+        match bit_depth {
+            16 => (value >> 8) as u8,
+            _ => (value as u32 * 255 / ((1 << bit_depth) - 1)) as u8,
+        }
+    };
+    // For color types without alpha, tRNS holds the one color that is transparent
+    // in 16 bits per channel whatever the bit depth
+    let transparent = |color: &[u16]| {
+        transparency.len() == color.len() * 2
+            && color.iter().enumerate().all(|(i, &value)| {
+                u16::from_be_bytes([transparency[i * 2], transparency[i * 2 + 1]]) == value
+            })
+    };
+
+    let mut rgba = Vec::with_capacity(width_px * height_px * 4);
+    for y in 0..height_px {
+        let row = &pixels[y * (stride + 1) + 1..][..stride];
+
+        for x in 0..width_px {
+            let pixel = match color_type {
+                0 => {
+                    let gray = sample(row, x);
+                    let value = scale(gray);
+                    [
+                        value,
+                        value,
+                        value,
+                        if transparent(&[gray]) { 0 } else { 0xFF },
+                    ]
+                }
+                2 => {
+                    let color = [
+                        sample(row, x * 3),
+                        sample(row, x * 3 + 1),
+                        sample(row, x * 3 + 2),
+                    ];
+                    let alpha = if transparent(&color) { 0 } else { 0xFF };
+                    [scale(color[0]), scale(color[1]), scale(color[2]), alpha]
+                }
+                3 => {
+                    let index = sample(row, x) as usize;
+                    let color = palette
+                        .get(index * 3..index * 3 + 3)
+                        .ok_or(PeError::InvalidIcon)?;
+                    let alpha = transparency.get(index).copied().unwrap_or(0xFF);
+                    [color[0], color[1], color[2], alpha]
+                }
+                4 => {
+                    let value = scale(sample(row, x * 2));
+                    [value, value, value, scale(sample(row, x * 2 + 1))]
+                }
+                _ => [0, 1, 2, 3].map(|channel| scale(sample(row, x * 4 + channel))),
+            };
+
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    Ok(IconImage {
+        width,
+        height,
+        rgba,
+    })
+}
+
+/// Reverses the filter each row starts with, in place, leaving the filter bytes as they are
+fn unfilter(pixels: &mut [u8], stride: usize, pixel_len: usize, height: usize) -> Result<()> {
+    for y in 0..height {
+        let (above, rest) = pixels.split_at_mut(y * (stride + 1));
+        let above = match y {
+            0 => None,
+            _ => Some(&above[(y - 1) * (stride + 1) + 1..]),
+        };
+        let filter = rest[0];
+        let row = &mut rest[1..=stride];
+
+        for i in 0..stride {
+            let left = if i >= pixel_len {
+                row[i - pixel_len]
+            } else {
+                0
+            };
+            let up = above.map_or(0, |above| above[i]);
+            let up_left = match above {
+                Some(above) if i >= pixel_len => above[i - pixel_len],
+                _ => 0,
+            };
+
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(PeError::InvalidIcon),
+            };
+            row[i] = row[i].wrapping_add(prediction);
+        }
+    }
+
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance = |value: u8| (estimate - value as i16).abs();
+
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// Decompresses a zlib stream, without checking its checksum. Streams that decompress to more
+/// than `limit` bytes are rejected.
+fn inflate_zlib(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let [method, flags, ..] = *data else {
+        return Err(PeError::InvalidIcon);
+    };
+
+    // DEFLATE without a preset dictionary
+    if method & 0x0F != 8
+        || !(method as u16 * 256 + flags as u16).is_multiple_of(31)
+        || flags & 0x20 != 0
+    {
+        return Err(PeError::InvalidIcon);
+    }
+
+    inflate(&data[2..], limit)
+}
+
+/// The order the lengths of the code length codes are stored in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Decompresses a raw DEFLATE stream, see RFC 1951
+fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut bits = Bits { data, position: 0 };
+    let mut out = Vec::new();
+
+    loop {
+        let last = bits.read(1)? == 1;
+
+        match bits.read(2)? {
+            0 => {
+                // Stored blocks start at a byte boundary
+                let start = bits.position.div_ceil(8);
+                let header = data.get(start..start + 4).ok_or(PeError::InvalidIcon)?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if out.len() + len > limit {
+                    return Err(PeError::InvalidIcon);
+                }
+
+                out.extend_from_slice(
+                    data.get(start + 4..start + 4 + len)
+                        .ok_or(PeError::InvalidIcon)?,
+                );
+                bits.position = (start + 4 + len) * 8;
+            }
+            1 => {
+                let mut lengths = [0; 288 + 30];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..288].fill(8);
+                lengths[288..].fill(5);
+
+                let literals = Huffman::new(&lengths[..288])?;
+                let distances = Huffman::new(&lengths[288..])?;
+                inflate_block(&mut bits, &mut out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let literal_count = bits.read(5)? as usize + 257;
+                let distance_count = bits.read(5)? as usize + 1;
+                let code_length_count = bits.read(4)? as usize + 4;
+
+                let mut code_lengths = [0; 19];
+                for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+                    code_lengths[i] = bits.read(3)? as u8;
+                }
+                let code_lengths = Huffman::new(&code_lengths)?;
+
+                let mut lengths = Vec::with_capacity(literal_count + distance_count);
+                while lengths.len() < literal_count + distance_count {
+                    let (value, repeat) = match code_lengths.decode(&mut bits)? {
+                        length @ 0..=15 => (length as u8, 1),
+                        16 => {
+                            let previous = *lengths.last().ok_or(PeError::InvalidIcon)?;
+                            (previous, 3 + bits.read(2)?)
+                        }
+                        17 => (0, 3 + bits.read(3)?),
+                        _ => (0, 11 + bits.read(7)?),
+                    };
+                    lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                }
+                if lengths.len() != literal_count + distance_count {
+                    return Err(PeError::InvalidIcon);
+                }
+
+                let literals = Huffman::new(&lengths[..literal_count])?;
+                let distances = Huffman::new(&lengths[literal_count..])?;
+                inflate_block(&mut bits, &mut out, limit, &literals, &distances)?;
+            }
+            _ => return Err(PeError::InvalidIcon),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<()> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        if symbol != 256 && out.len() >= limit {
+            return Err(PeError::InvalidIcon);
+        }
+
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                let length = *LENGTH_BASE.get(i).ok_or(PeError::InvalidIcon)? as usize
+                    + bits.read(LENGTH_EXTRA[i])? as usize;
+
+                let i = distances.decode(bits)? as usize;
+                let distance = *DISTANCE_BASE.get(i).ok_or(PeError::InvalidIcon)? as usize
+                    + bits.read(DISTANCE_EXTRA[i])? as usize;
+
+                let start = out
+                    .len()
+                    .checked_sub(distance)
+                    .ok_or(PeError::InvalidIcon)?;
+                if out.len() + length > limit {
+                    return Err(PeError::InvalidIcon);
+                }
+                // The copy can overlap what it is appending
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+/// Reads bits from the least significant end of each byte
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn read(&mut self, count: u8) -> Result<u32> {
+        let mut value = 0;
+
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or(PeError::InvalidIcon)?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+/// A canonical Huffman code, decoded one bit at a time
+struct Huffman {
+    /// The number of codes of each length
+    counts: [u16; 16],
+    /// The symbols ordered by their codes
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Huffman> {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            *counts
+                .get_mut(length as usize)
+                .ok_or(PeError::InvalidIcon)? += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16> {
+        // The first code of each length, and the index of its symbol
+        let (mut code, mut first, mut index) = (0, 0, 0);
+
+        for &count in &self.counts[1..] {
+            code |= bits.read(1)? as i32;
+
+            if code - first < count as i32 {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count as i32;
+            first = (first + count as i32) << 1;
+            code <<= 1;
+        }
+
+        Err(PeError::InvalidIcon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "Stored as is" in a stored block
+    const STORED: &[u8] = &[
+        0x78, 0x01, 0x01, 0x0C, 0x00, 0xF3, 0xFF, 0x53, 0x74, 0x6F, 0x72, 0x65, 0x64, 0x20, 0x61,
+        0x73, 0x20, 0x69, 0x73, 0x1D, 0x3E, 0x04, 0x62,
+    ];
+    /// "abcabcabcabc fixed" in a block with the fixed codes
+    const FIXED: &[u8] = &[
+        0x78, 0x01, 0x4B, 0x4C, 0x4A, 0x4E, 0x84, 0x21, 0x85, 0xB4, 0xCC, 0x8A, 0xD4, 0x14, 0x00,
+        0x40, 0x6E, 0x06, 0xC9,
+    ];
+    /// The lines of `numbered_lines` in a block with its own codes
+    const DYNAMIC: &[u8] = &[
+        0x78, 0xDA, 0x6D, 0xCF, 0xBB, 0x0D, 0x80, 0x30, 0x10, 0x04, 0xD1, 0x9C, 0x2A, 0xAE, 0x04,
+        0x96, 0x3F, 0x05, 0x19, 0x61, 0xC9, 0x82, 0xE4, 0x02, 0xCA, 0x47, 0x84, 0x68, 0x27, 0x7D,
+        0xD1, 0x4C, 0xAB, 0x57, 0x89, 0x3E, 0xEE, 0x23, 0xF2, 0x2C, 0x91, 0xE5, 0xC9, 0xAE, 0x7D,
+        0x24, 0xA7, 0xC1, 0x69, 0x74, 0x9A, 0x9C, 0x66, 0xA7, 0xC5, 0x69, 0x75, 0xDA, 0x9C, 0x76,
+        0x48, 0xA5, 0x7C, 0xE8, 0x17, 0x0C, 0x08, 0x0E, 0x04, 0x0B, 0x82, 0x07, 0xC1, 0x84, 0xE0,
+        0x42, 0xB0, 0xA1, 0xFF, 0xC7, 0x0B, 0x89, 0x56, 0x7F, 0x09,
+    ];
+
+    fn numbered_lines() -> Vec<u8> {
+        (0..20)
+            .flat_map(|i| format!("line {i} of the text\n").into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(inflate_zlib(STORED, 0x100).unwrap(), b"Stored as is");
+        assert_eq!(inflate_zlib(FIXED, 0x100).unwrap(), b"abcabcabcabc fixed");
+
+        let lines = numbered_lines();
+        assert_eq!(inflate_zlib(DYNAMIC, lines.len()).unwrap(), lines);
+    }
+
+    #[test]
+    fn truncated_streams() {
+        for stream in [STORED, FIXED, DYNAMIC] {
+            // The last 4 bytes are the checksum, which is not read
+            for len in 0..stream.len() - 4 {
+                assert!(matches!(
+                    inflate_zlib(&stream[..len], 0x1000),
+                    Err(PeError::InvalidIcon)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn output_is_limited() {
+        assert!(inflate_zlib(STORED, 11).is_err());
+        assert!(inflate_zlib(FIXED, 17).is_err());
+        assert!(inflate_zlib(DYNAMIC, numbered_lines().len() - 1).is_err());
+    }
+
+    /// Puts image data in a stored block, as an encoder that does not compress would
+    fn png(header: [u8; 13], chunks: &[(&[u8; 4], &[u8])], data: &[u8]) -> Vec<u8> {
+        let mut idat = vec![0x78, 0x01, 0x01];
+        idat.extend((data.len() as u16).to_le_bytes());
+        idat.extend((!data.len() as u16).to_le_bytes());
+        idat.extend(data);
+        idat.extend([0; 4]);
+
+        let mut png = SIGNATURE.to_vec();
+        let chunks = [(b"IHDR", &header[..])]
+            .into_iter()
+            .chain(chunks.iter().copied())
+            .chain([(b"IDAT", &idat[..]), (b"IEND", &[][..])]);
+        for (kind, chunk) in chunks {
+            png.extend((chunk.len() as u32).to_be_bytes());
+            png.extend(kind);
+            png.extend(chunk);
+            png.extend([0; 4]);
+        }
+
+        png
+    }
+
+    fn header(width: u32, height: u32, bit_depth: u8, color_type: u8) -> [u8; 13] {
+        let mut header = [0; 13];
+        header[..4].copy_from_slice(&width.to_be_bytes());
+        header[4..8].copy_from_slice(&height.to_be_bytes());
+        header[8] = bit_depth;
+        header[9] = color_type;
+        header
+    }
+
+    #[test]
+    fn filtered_rgba() {
+        #[rustfmt::skip]
+        let data = [
+            // None
+            0, 10, 20, 30, 255, 40, 50, 60, 255,
+            // Sub
+            1, 1, 2, 3, 0, 1, 1, 1, 0,
+            // Up
+            2, 1, 1, 1, 0, 1, 1, 1, 0,
+            // Average
+            3, 5, 5, 5, 127, 5, 5, 5, 0,
+            // Paeth
+            4, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let image = decode(&png(header(2, 5, 8, 6), &[], &data)).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(image.rgba, [
+            10, 20, 30, 255, 40, 50, 60, 255,
+            1, 2, 3, 0, 2, 3, 4, 0,
+            2, 3, 4, 0, 3, 4, 5, 0,
+            6, 6, 7, 127, 9, 10, 11, 63,
+            6, 6, 7, 127, 9, 10, 11, 63,
+        ]);
+        assert_eq!((image.width, image.height), (2, 5));
+    }
+
+    #[test]
+    fn palette() {
+        let palette = [0, 0, 0, 0xFF, 0, 0];
+        let transparency = [0];
+        // Two bits per pixel, with one index past the palette
+        let data = [0, 0b0001_0000];
+        let png = |data: &[u8]| {
+            png(
+                header(3, 1, 2, 3),
+                &[(b"PLTE", &palette[..]), (b"tRNS", &transparency[..])],
+                data,
+            )
+        };
+
+        let image = decode(&png(&data)).unwrap();
+        assert_eq!(image.rgba, [0, 0, 0, 0, 0xFF, 0, 0, 0xFF, 0, 0, 0, 0]);
+
+        assert!(matches!(
+            decode(&png(&[0, 0b0010_0000])),
+            Err(PeError::InvalidIcon)
+        ));
+        // More data than the image needs
+        assert!(matches!(
+            decode(&png(&[0, 0, 0])),
+            Err(PeError::InvalidIcon)
+        ));
+    }
+}
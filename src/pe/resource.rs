@@ -0,0 +1,143 @@
+use std::fmt;
+
+use super::{ffi, read, PeError, PeImage, Result};
+
+pub const RT_ICON: u16 = 3;
+pub const RT_GROUP_ICON: u16 = 14;
+pub const RT_VERSION: u16 = 16;
+
+/// Resources are named either by a number or by a string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResourceId {
+    Id(u16),
+    Name(String),
+}
+
+impl fmt::Display for ResourceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceId::Id(id) => write!(f, "#{id}"),
+            ResourceId::Name(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// The data of a resource in one language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource<'a> {
+    pub id: ResourceId,
+    pub language: u16,
+    pub code_page: u32,
+    pub data: &'a [u8],
+}
+
+impl PeImage {
+    /// Returns every resource of a type, such as [RT_VERSION], in the order they are stored.
+    ///
+    /// The resource directory is three levels deep, with the types at the top, then the names of
+    /// the resources, and then the languages they are in.
+    pub fn resources(&self, kind: u16) -> Result<Vec<Resource<'_>>> {
+        let Some(directory) = self.directory(super::directory::RESOURCE) else {
+            return Ok(Vec::new());
+        };
+        let root = self.bytes_from(directory.virtual_address)?;
+
+        let mut resources = Vec::new();
+
+        for (id, types) in entries(root, 0)? {
+            if id != ResourceId::Id(kind) {
+                continue;
+            }
+            let Entry::Directory(names) = types else {
+                return Err(PeError::InvalidResource);
+            };
+
+            for (id, languages) in entries(root, names)? {
+                let languages = match languages {
+                    Entry::Directory(languages) => entries(root, languages)?,
+                    // Some packers leave out the level of languages
+                    data => vec![(ResourceId::Id(0), data)],
+                };
+
+                for (language, data) in languages {
+                    let Entry::Data(data) = data else {
+                        return Err(PeError::InvalidResource);
+                    };
+                    let entry = read::<ffi::ResourceDataEntry>(root, data)?;
+
+                    resources.push(Resource {
+                        id: id.clone(),
+                        language: match language {
+                            ResourceId::Id(language) => language,
+                            ResourceId::Name(_) => 0,
+                        },
+                        code_page: entry.code_page,
+                        data: self.bytes_at(entry.offset_to_data, entry.size as usize)?,
+                    });
+                }
+            }
+        }
+
+        Ok(resources)
+    }
+
+    /// Returns a resource by its type and id, in the first language it is in
+    pub fn resource(&self, kind: u16, id: &ResourceId) -> Result<Option<Resource<'_>>> {
+        Ok(self
+            .resources(kind)?
+            .into_iter()
+            .find(|resource| resource.id == *id))
+    }
+}
+
+enum Entry {
+    /// The offset of a directory one level down
+    Directory(usize),
+    /// The offset of a [ffi::ResourceDataEntry]
+    Data(usize),
+}
+
+/// Reads the entries of the directory at an offset from the root of the resources
+fn entries(root: &[u8], offset: usize) -> Result<Vec<(ResourceId, Entry)>> {
+    let directory = read::<ffi::ResourceDirectory>(root, offset)?;
+    let count =
+        directory.number_of_named_entries as usize + directory.number_of_id_entries as usize;
+
+    (0..count)
+        .map(|i| {
+            let entry = read::<ffi::ResourceDirectoryEntry>(
+                root,
+                offset + std::mem::size_of::<ffi::ResourceDirectory>() + i * 8,
+            )?;
+
+            let id = match entry.name & 0x8000_0000 {
+                0 => ResourceId::Id(entry.name as u16),
+                _ => ResourceId::Name(read_name(root, (entry.name & 0x7FFF_FFFF) as usize)?),
+            };
+
+            let target = (entry.offset_to_data & 0x7FFF_FFFF) as usize;
+            let entry = match entry.offset_to_data & 0x8000_0000 {
+                0 => Entry::Data(target),
+                _ => Entry::Directory(target),
+            };
+
+            Ok((id, entry))
+        })
+        .collect()
+}
+
+/// Reads a name, which is stored as a UTF-16 string prefixed with its length
+fn read_name(root: &[u8], offset: usize) -> Result<String> {
+    let len = read::<u16>(root, offset)? as usize;
+    let bytes = root
+        .get(offset + 2..offset + 2 + len * 2)
+        .ok_or(PeError::InvalidResource)?;
+
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+
+    Ok(char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect())
+}
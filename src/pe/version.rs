@@ -0,0 +1,343 @@
+use super::{ffi, read, resource::RT_VERSION, PeError, PeImage, Result};
+use crate::memory::ffi::TranslationEntry;
+
+/// The signature `VS_FIXEDFILEINFO` starts with
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF04BD;
+
+/// The numeric part of the version information
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedFileInfo {
+    /// The major, minor, patch and build numbers of the file
+    pub file_version: [u16; 4],
+    /// The major, minor, patch and build numbers of the product the file is a part of
+    pub product_version: [u16; 4],
+    pub file_flags: u32,
+    pub file_os: u32,
+    pub file_type: u32,
+    pub file_subtype: u32,
+}
+
+/// The strings of the version information in one language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringTable {
+    pub language: u16,
+    pub code_page: u16,
+    pub strings: Vec<(String, String)>,
+}
+
+impl StringTable {
+    /// Returns a string by its name, ignoring case like `VerQueryValueW` does
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.strings
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The `VS_VERSIONINFO` resource, which holds the description, product name, version and such
+/// of an executable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    pub fixed: Option<FixedFileInfo>,
+    pub string_tables: Vec<StringTable>,
+    /// The languages and code pages listed in `VarFileInfo`, which should each have a string
+    /// table
+    pub translations: Vec<TranslationEntry>,
+}
+
+impl VersionInfo {
+    pub fn parse(data: &[u8]) -> Result<VersionInfo> {
+        let root = Block::parse(data)?;
+        if root.key != "VS_VERSION_INFO" {
+            return Err(PeError::InvalidVersion);
+        }
+
+        let fixed = read::<ffi::FixedFileInfo>(root.value, 0)
+            .ok()
+            .filter(|fixed| fixed.signature == FIXED_FILE_INFO_SIGNATURE)
+            .map(|fixed| FixedFileInfo {
+                file_version: split_version(fixed.file_version_ms, fixed.file_version_ls),
+                product_version: split_version(fixed.product_version_ms, fixed.product_version_ls),
+                file_flags: fixed.file_flags & fixed.file_flags_mask,
+                file_os: fixed.file_os,
+                file_type: fixed.file_type,
+                file_subtype: fixed.file_subtype,
+            });
+
+        let mut info = VersionInfo {
+            fixed,
+            string_tables: Vec::new(),
+            translations: Vec::new(),
+        };
+
+        for child in root.children() {
+            let child = child?;
+
+            match child.key.as_str() {
+                "StringFileInfo" => {
+                    for table in child.children() {
+                        let table = table?;
+
+                        // The key is the language and the code page in hexadecimal, e.g. 041104B0
+                        let id = u32::from_str_radix(&table.key, 16)
+                            .map_err(|_| PeError::InvalidVersion)?;
+
+                        let strings = table
+                            .children()
+                            .map(|string| {
+                                let string = string?;
+                                Ok((string.key, decode_utf16(string.value)))
+                            })
+                            .collect::<Result<_>>()?;
+
+                        info.string_tables.push(StringTable {
+                            language: (id >> 16) as u16,
+                            code_page: id as u16,
+                            strings,
+                        });
+                    }
+                }
+                "VarFileInfo" => {
+                    for var in child.children() {
+                        let var = var?;
+
+                        if var.key == "Translation" {
+                            info.translations.extend(
+                                var.value
+                                    .chunks_exact(std::mem::size_of::<TranslationEntry>())
+                                    .map(bytemuck::pod_read_unaligned::<TranslationEntry>),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn string_table(&self, language: u16, code_page: u16) -> Option<&StringTable> {
+        self.string_tables
+            .iter()
+            .find(|table| table.language == language && table.code_page == code_page)
+    }
+
+    /// Returns a string in a language, like `VerQueryValueW` with
+    /// `\StringFileInfo\{language}{code_page}\{name}`
+    pub fn get_string(&self, language: u16, code_page: u16, name: &str) -> Option<&str> {
+        self.string_table(language, code_page)?.get(name)
+    }
+
+    /// Returns the languages the strings are in, the listed translations first.
+    ///
+    /// The translations do not always match the string tables, so only the ones that have a table
+    /// are returned, followed by the tables that are not listed.
+    pub fn languages(&self) -> Vec<TranslationEntry> {
+        let tables = self.string_tables.iter().map(|table| TranslationEntry {
+            language: table.language,
+            code_page: table.code_page,
+        });
+
+        let mut languages = self
+            .translations
+            .iter()
+            .copied()
+            .filter(|entry| self.string_table(entry.language, entry.code_page).is_some())
+            .collect::<Vec<_>>();
+        for entry in tables {
+            if !languages.contains(&entry) {
+                languages.push(entry);
+            }
+        }
+
+        languages
+    }
+}
+
+impl PeImage {
+    /// Returns the version information of the image, if it has any
+    pub fn version_info(&self) -> Result<Option<VersionInfo>> {
+        self.resources(RT_VERSION)?
+            .first()
+            .map(|resource| VersionInfo::parse(resource.data))
+            .transpose()
+    }
+}
+
+/// A node of the version information, each of which starts with its length and a key, followed
+/// by a value and more nodes, all aligned to 4 bytes
+struct Block<'a> {
+    key: String,
+    value: &'a [u8],
+    children: &'a [u8],
+}
+
+impl<'a> Block<'a> {
+    fn parse(data: &'a [u8]) -> Result<Block<'a>> {
+        let length = read::<u16>(data, 0).map_err(|_| PeError::InvalidVersion)? as usize;
+        let value_length = read::<u16>(data, 2).map_err(|_| PeError::InvalidVersion)? as usize;
+        let is_text = read::<u16>(data, 4).map_err(|_| PeError::InvalidVersion)? == 1;
+
+        // The length counts the header, so anything shorter is not a block
+        if length < 6 {
+            return Err(PeError::InvalidVersion);
+        }
+        let data = data.get(..length).ok_or(PeError::InvalidVersion)?;
+
+        let key_len = data[6..]
+            .chunks_exact(2)
+            .position(|unit| unit == [0, 0])
+            .ok_or(PeError::InvalidVersion)?;
+        let key = decode_utf16(&data[6..6 + key_len * 2]);
+
+        // The length of text is counted in characters, though not every compiler agrees on that
+        let value_start = align(6 + key_len * 2 + 2).min(length);
+        let value_len = if is_text {
+            value_length * 2
+        } else {
+            value_length
+        };
+        let value_end = (value_start + value_len).min(length);
+
+        Ok(Block {
+            key,
+            value: &data[value_start..value_end],
+            children: &data[align(value_end).min(length)..],
+        })
+    }
+
+    fn children(&self) -> impl Iterator<Item = Result<Block<'a>>> {
+        let mut rest = self.children;
+
+        std::iter::from_fn(move || {
+            // Blocks can be followed by padding
+            if rest.len() < 6 {
+                return None;
+            }
+
+            // A length shorter than the header or past the parent would not move on correctly
+            let length = read::<u16>(rest, 0).map_or(0, |length| length as usize);
+            if !(6..=rest.len()).contains(&length) {
+                rest = &[];
+                return Some(Err(PeError::InvalidVersion));
+            }
+
+            let block = Block::parse(rest);
+            rest = rest.get(align(length)..).unwrap_or_default();
+
+            Some(block)
+        })
+    }
+}
+
+fn align(offset: usize) -> usize {
+    offset.next_multiple_of(4)
+}
+
+/// Decodes UTF-16 up to the first NUL
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .take_while(|&unit| unit != 0);
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+fn split_version(most_significant: u32, least_significant: u32) -> [u16; 4] {
+    [
+        (most_significant >> 16) as u16,
+        most_significant as u16,
+        (least_significant >> 16) as u16,
+        least_significant as u16,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::{tests::VERSION_DLL, Layout};
+
+    #[test]
+    fn fixture() {
+        let image = PeImage::parse(VERSION_DLL.to_vec(), Layout::File).unwrap();
+        let info = image.version_info().unwrap().unwrap();
+
+        assert_eq!(
+            info.fixed,
+            Some(FixedFileInfo {
+                file_version: [1, 2, 3, 4],
+                product_version: [5, 6, 0, 0],
+                file_flags: 0x2,
+                file_os: 0x40004,
+                file_type: 0x2,
+                file_subtype: 0,
+            })
+        );
+
+        assert_eq!(
+            info.get_string(0x411, 1200, "FileDescription"),
+            Some("テスト")
+        );
+        assert_eq!(
+            info.get_string(0x409, 1200, "productname"),
+            Some("Textractor fixture")
+        );
+        assert_eq!(info.get_string(0x409, 1200, "ProductVersion"), Some("5.6"));
+        assert_eq!(info.get_string(0x411, 1200, "ProductVersion"), None);
+        assert_eq!(info.get_string(0x409, 1252, "ProductName"), None);
+
+        // 0409/1252 is listed but has no table, while 0409/1200 has a table but is not listed
+        let entry = |language, code_page| TranslationEntry {
+            language,
+            code_page,
+        };
+        assert_eq!(info.translations, [entry(0x411, 1200), entry(0x409, 1252)]);
+        assert_eq!(info.languages(), [entry(0x411, 1200), entry(0x409, 1200)]);
+    }
+
+    /// Builds a block with a binary value and the given children
+    fn block(key: &str, value: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 6];
+        data[2..4].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        data.extend(key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+        data.resize(align(data.len()), 0);
+        data.extend(value);
+        data.resize(align(data.len()), 0);
+        data.extend(children);
+
+        let len = data.len() as u16;
+        data[..2].copy_from_slice(&len.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        let invalid =
+            |data: &[u8]| matches!(VersionInfo::parse(data), Err(PeError::InvalidVersion));
+
+        assert!(invalid(&[]));
+        assert!(invalid(&[0; 6]));
+        assert!(invalid(&[4, 0, 0, 0, 0, 0, 0, 0]));
+        assert!(invalid(&block("StringFileInfo", &[], &[])));
+
+        let empty = block("VS_VERSION_INFO", &[], &[]);
+        assert_eq!(VersionInfo::parse(&empty).unwrap().fixed, None);
+
+        // The length of a child is checked before it is parsed
+        for length in [0u16, 4, 0x100] {
+            let mut child = block("VarFileInfo", &[], &[]);
+            child[..2].copy_from_slice(&length.to_le_bytes());
+            assert!(invalid(&block("VS_VERSION_INFO", &[], &child)));
+        }
+
+        let translation = block("Translation", &[0x11, 0x04, 0xB0, 0x04], &[]);
+        let var = block("VarFileInfo", &[], &translation);
+        let info = VersionInfo::parse(&block("VS_VERSION_INFO", &[], &var)).unwrap();
+        assert_eq!(info.translations.len(), 1);
+    }
+}
@@ -0,0 +1,181 @@
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use crate::memory::{MemorySource, RemoteAddress, Result};
+
+pub mod pattern;
+mod storage;
+pub mod strings;
+pub mod text_diff;
+pub mod value;
+
+pub use pattern::Pattern;
+
+const PAGE_SIZE: usize = 0x1000;
+const CHUNK_SIZE: usize = 0x10_0000;
+
+/// Reads the readable memory overlapping `range` in chunks, calling `f` with the address of each
+/// chunk and its contents.
+///
+/// Adjacent readable regions are read as one, and consecutive chunks overlap by `overlap` bytes,
+/// so that anything up to `overlap + 1` bytes long is found by looking within single chunks. It
+/// is never found twice as long as only matches that end inside a chunk are reported. Pages that
+/// fail to read are skipped, and an error returned by `f` stops the walk.
+pub fn for_each_chunk<S: MemorySource + ?Sized>(
+    source: &S,
+    range: Range<RemoteAddress>,
+    overlap: usize,
+    mut f: impl FnMut(RemoteAddress, &[u8]) -> Result<()>,
+) -> Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE + overlap];
+
+    for span in readable_spans(source, range)? {
+        let mut cursor = span.start;
+
+        while cursor < span.end {
+            let len = (span.end - cursor).min(buffer.len());
+            let read = source.read(cursor, &mut buffer[..len]).unwrap_or(0);
+
+            if read == 0 {
+                cursor = next_page(cursor).min(span.end);
+                continue;
+            }
+
+            f(cursor, &buffer[..read])?;
+
+            cursor = if read < len {
+                // The read stopped at a page that can not be read, so nothing can span it
+                next_page(cursor + read).min(span.end)
+            } else if cursor + read < span.end {
+                cursor + (read - overlap.min(read - 1))
+            } else {
+                span.end
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the readable parts of `range`, merging regions that follow each other
+fn readable_spans<S: MemorySource + ?Sized>(
+    source: &S,
+    range: Range<RemoteAddress>,
+) -> Result<Vec<Range<RemoteAddress>>> {
+    let mut spans: Vec<Range<RemoteAddress>> = Vec::new();
+
+    for region in source.regions()? {
+        if !region.is_readable() {
+            continue;
+        }
+
+        let start = region.base.max(range.start);
+        let end = region.end().min(range.end);
+        if start >= end {
+            continue;
+        }
+
+        match spans.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => spans.push(start..end),
+        }
+    }
+
+    Ok(spans)
+}
+
+fn next_page(address: RemoteAddress) -> RemoteAddress {
+    RemoteAddress::new((address.get() | (PAGE_SIZE - 1)).saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{
+        mock::{Fault, MockProcess},
+        Error, Protection,
+    };
+
+    const BASE: usize = 0x10000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    /// Returns the address and length of each chunk
+    fn chunks(process: &MockProcess, range: Range<usize>, overlap: usize) -> Vec<(usize, usize)> {
+        let mut chunks = Vec::new();
+        for_each_chunk(
+            process,
+            addr(range.start)..addr(range.end),
+            overlap,
+            |address, data| {
+                chunks.push((address.get(), data.len()));
+                Ok(())
+            },
+        )
+        .unwrap();
+        chunks
+    }
+
+    #[test]
+    fn overlap() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 2 * CHUNK_SIZE, Protection::READ);
+        // Adjacent regions are read as one
+        process.map_zeroed(addr(BASE + 2 * CHUNK_SIZE), 0x2000, Protection::READ_WRITE);
+
+        assert_eq!(
+            chunks(&process, 0..usize::MAX, 0x10),
+            [
+                (BASE, CHUNK_SIZE + 0x10),
+                (BASE + CHUNK_SIZE, CHUNK_SIZE + 0x10),
+                (BASE + 2 * CHUNK_SIZE, 0x2000),
+            ]
+        );
+
+        // The last chunk is not read again just for the overlap
+        assert_eq!(
+            chunks(&process, BASE..BASE + CHUNK_SIZE + 0x10, 0x10),
+            [(BASE, CHUNK_SIZE + 0x10)]
+        );
+        assert_eq!(
+            chunks(&process, BASE + 0x100..BASE + 0x200, 0),
+            [(BASE + 0x100, 0x100)]
+        );
+    }
+
+    #[test]
+    fn unreadable_memory() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 0x3000, Protection::READ);
+        process.map_zeroed(addr(BASE + 0x3000), 0x1000, Protection::NONE);
+        process.map_zeroed(addr(BASE + 0x4000), 0x1000, Protection::READ);
+        // Reads stop here, as if the page was unmapped while being read
+        process.inject_fault(addr(BASE + 0x1800)..addr(BASE + 0x1900), Fault::Partial);
+
+        assert_eq!(
+            chunks(&process, 0..usize::MAX, 0x10),
+            [
+                (BASE, 0x1800),
+                (BASE + 0x2000, 0x1000),
+                (BASE + 0x4000, 0x1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_on_error() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 3 * CHUNK_SIZE, Protection::READ);
+
+        let mut calls = 0;
+        let result = for_each_chunk(&process, addr(0)..addr(usize::MAX), 0, |address, _| {
+            calls += 1;
+            Err(Error::InvalidAddress(address))
+        });
+        assert!(matches!(result, Err(Error::InvalidAddress(address)) if address == addr(BASE)));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+use memchr::memmem;
+use thiserror::Error;
+
+use crate::memory::{MemorySource, RemoteAddress};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    #[error("The pattern is empty")]
+    Empty,
+    #[error("{0:?} is neither a hexadecimal byte nor a wildcard")]
+    InvalidByte(String),
+    #[error("The pattern only consists of wildcards")]
+    OnlyWildcards,
+}
+
+/// A byte pattern with wildcards, such as `48 8B ?? ?? 89 05 ?? ?? ?? ??`.
+///
+/// Bytes are written as two hexadecimal digits and wildcards as `??` or `?`, separated by
+/// whitespace. Searching looks for the longest run of known bytes first, and only compares the
+/// rest of the pattern where that run was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<Option<u8>>,
+    /// The longest run of bytes without wildcards
+    anchor: Range<usize>,
+}
+
+impl Pattern {
+    pub fn new(bytes: Vec<Option<u8>>) -> Result<Pattern, PatternError> {
+        if bytes.is_empty() {
+            return Err(PatternError::Empty);
+        }
+
+        let mut anchor = 0..0;
+        let mut start = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            if byte.is_none() {
+                start = i + 1;
+            } else if i + 1 - start > anchor.len() {
+                anchor = start..i + 1;
+            }
+        }
+
+        if anchor.is_empty() {
+            return Err(PatternError::OnlyWildcards);
+        }
+
+        Ok(Pattern { bytes, anchor })
+    }
+
+    /// Returns the length of the pattern in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn bytes(&self) -> &[Option<u8>] {
+        &self.bytes
+    }
+
+    /// Returns whether the pattern matches the start of `data`
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(data)
+                .all(|(byte, actual)| byte.is_none_or(|byte| byte == *actual))
+    }
+
+    /// Returns the offset of every match in `haystack`, including overlapping ones
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        let needle = self.bytes[self.anchor.clone()]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let finder = memmem::Finder::new(&needle).into_owned();
+
+        let mut position = 0;
+        std::iter::from_fn(move || loop {
+            let found = position + finder.find(haystack.get(position..)?)?;
+            position = found + 1;
+
+            let Some(start) = found.checked_sub(self.anchor.start) else {
+                continue;
+            };
+
+            if haystack.get(start..).is_some_and(|data| self.matches(data)) {
+                return Some(start);
+            }
+        })
+    }
+
+    /// Returns the address of every match in the readable memory of `range`
+    pub fn scan_range<S: MemorySource + ?Sized>(
+        &self,
+        source: &S,
+        range: Range<RemoteAddress>,
+    ) -> crate::memory::Result<Vec<RemoteAddress>> {
+        let mut matches = Vec::new();
+
+        super::for_each_chunk(source, range, self.len() - 1, |address, data| {
+            matches.extend(self.find_iter(data).map(|offset| address + offset));
+
+            Ok(())
+        })?;
+
+        Ok(matches)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s
+            .split_whitespace()
+            .map(|token| match token {
+                "?" | "??" => Ok(None),
+                _ => match token.as_bytes() {
+                    [a, b] if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => {
+                        Ok(u8::from_str_radix(token, 16).ok())
+                    }
+                    _ => Err(PatternError::InvalidByte(token.into())),
+                },
+            })
+            .collect::<Result<_, _>>()?;
+
+        Pattern::new(bytes)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+
+            match byte {
+                Some(byte) => write!(f, "{byte:02X}")?,
+                None => f.write_str("??")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::{mock::MockProcess, Protection},
+        scan::CHUNK_SIZE,
+    };
+
+    const BASE: usize = 0x10000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    fn pattern(s: &str) -> Pattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        let pattern = pattern("48 8b ?? ? 89 05");
+        assert_eq!(
+            pattern.bytes(),
+            [Some(0x48), Some(0x8B), None, None, Some(0x89), Some(0x05)]
+        );
+        assert_eq!(pattern.to_string(), "48 8B ?? ?? 89 05");
+
+        assert_eq!("".parse::<Pattern>(), Err(PatternError::Empty));
+        assert_eq!("?? ?".parse::<Pattern>(), Err(PatternError::OnlyWildcards));
+        for token in ["4G", "123", "*"] {
+            assert_eq!(
+                format!("48 {token}").parse::<Pattern>(),
+                Err(PatternError::InvalidByte(token.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn anchor() {
+        // The longest run of known bytes is searched for, wherever it is
+        assert_eq!(pattern("AA ?? BB CC DD ?? EE").anchor, 2..5);
+        assert_eq!(pattern("?? ?? AA BB").anchor, 2..4);
+        // The first of two runs that are as long
+        assert_eq!(pattern("AA BB ?? CC DD").anchor, 0..2);
+    }
+
+    #[test]
+    fn wildcards() {
+        let pattern = pattern("48 8B ?? ?? 89 05");
+        let haystack = [
+            0x48, 0x8B, 0x01, 0x02, 0x89, 0x05, // A match
+            0x48, 0x8B, 0x03, 0x89, 0x05, // One byte short
+            0x48, 0x8B, 0x89, 0x05, 0x89, 0x05, // The known bytes fill in for the wildcards
+            0x48, 0x8B, 0x00, // Cut off
+        ];
+        assert_eq!(pattern.find_iter(&haystack).collect::<Vec<_>>(), [0, 11]);
+
+        // Matches may overlap
+        let pattern = self::pattern("AA ?? AA");
+        let found = pattern.find_iter(&[0xAA; 5]).collect::<Vec<_>>();
+        assert_eq!(found, [0, 1, 2]);
+    }
+
+    #[test]
+    fn leading_wildcards() {
+        let pattern = pattern("?? ?? AA BB ?? CC");
+        let haystack = [
+            0xAA, 0xBB, 0x00, 0xCC, // The anchor is found too close to the start
+            0x01, 0x02, 0xAA, 0xBB, 0x03, 0xCC, // A match
+            0xAA, 0xBB, 0x04, 0xDD, // The anchor without the rest
+            0x05, 0x06, 0xAA, 0xBB, 0x07, // Cut off
+        ];
+        assert_eq!(pattern.find_iter(&haystack).collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn scan_range() {
+        let pattern = pattern("DE AD ?? ?? BE EF");
+        let bytes = [0xDE, 0xAD, 0x00, 0x00, 0xBE, 0xEF];
+
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 2 * CHUNK_SIZE, Protection::READ_WRITE);
+        // Adjacent regions are searched as one
+        process.map_zeroed(addr(BASE + 2 * CHUNK_SIZE), 0x1000, Protection::READ_WRITE);
+        // Unreadable memory is skipped
+        process.map(addr(BASE + 0x300000), bytes, Protection::NONE);
+
+        let expected = [
+            BASE,
+            // Only the overlap with the second chunk completes this one
+            BASE + CHUNK_SIZE - 1,
+            // Right after the overlap, so only in the second chunk
+            BASE + CHUNK_SIZE + 5,
+            // Across the end of the first region
+            BASE + 2 * CHUNK_SIZE - 2,
+            // At the very end of the memory
+            BASE + 2 * CHUNK_SIZE + 0x1000 - bytes.len(),
+        ];
+        for address in expected {
+            process.write(addr(address), &bytes).unwrap();
+        }
+
+        let found = pattern
+            .scan_range(&process, addr(0)..addr(usize::MAX))
+            .unwrap();
+        assert_eq!(found, expected.map(addr));
+
+        // Matches have to lie entirely within the range
+        let range = addr(BASE + 1)..addr(BASE + 2 * CHUNK_SIZE + 4);
+        let found = pattern.scan_range(&process, range).unwrap();
+        assert_eq!(
+            found,
+            expected[1..4].iter().copied().map(addr).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::memory::RemoteAddress;
+
+/// Storage is moved to a temporary file once it takes up more memory than this. Tests spill much
+/// sooner, so that they do not need huge amounts of memory to get there.
+const SPILL_THRESHOLD: usize = if cfg!(test) { 0x1_0000 } else { 64 << 20 };
+
+/// Reads the next block of `[address: u64][size: u64][data]`
+pub(super) fn read_block(reader: &mut impl Read) -> io::Result<Option<(RemoteAddress, Vec<u8>)>> {
+    let mut header = [0; 16];
+    if !read_or_eof(reader, &mut header)? {
+        return Ok(None);
+    }
+
+    let address = u64::from_le_bytes(header[..8].try_into().unwrap());
+    let size = u64::from_le_bytes(header[8..].try_into().unwrap());
+
+    let mut data = vec![0; size as usize];
+    reader.read_exact(&mut data)?;
+
+    Ok(Some((RemoteAddress::new(address as usize), data)))
+}
+
+/// Reads up to `limit` records of `[address: u64][value]` with values of `size` bytes
+pub(super) fn read_records(
+    reader: &mut impl Read,
+    size: usize,
+    limit: usize,
+) -> io::Result<Vec<(RemoteAddress, Vec<u8>)>> {
+    let mut records = Vec::new();
+    let mut record = vec![0; 8 + size];
+
+    while records.len() < limit && read_or_eof(reader, &mut record)? {
+        let address = u64::from_le_bytes(record[..8].try_into().unwrap());
+        records.push((RemoteAddress::new(address as usize), record[8..].to_vec()));
+    }
+
+    Ok(records)
+}
+
+/// Fills the buffer, returning `false` if the reader was already at its end
+fn read_or_eof(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Scan results that are written once and then read from the start any number of times
+pub(super) enum Storage {
+    Memory(Vec<u8>),
+    File(TempFile),
+}
+
+impl Storage {
+    pub(super) fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(match self {
+            Storage::Memory(data) => Box::new(Cursor::new(data)),
+            Storage::File(file) => {
+                let mut file = &file.file;
+                file.seek(SeekFrom::Start(0))?;
+                Box::new(BufReader::new(file))
+            }
+        })
+    }
+}
+
+#[derive(Default)]
+pub(super) struct StorageWriter {
+    memory: Vec<u8>,
+    file: Option<BufWriter<TempFile>>,
+}
+
+impl StorageWriter {
+    pub(super) fn write_record(&mut self, address: RemoteAddress, value: &[u8]) -> io::Result<()> {
+        self.write(&(address.get() as u64).to_le_bytes())?;
+        self.write(value)
+    }
+
+    pub(super) fn write_block(&mut self, address: RemoteAddress, data: &[u8]) -> io::Result<()> {
+        self.write(&(address.get() as u64).to_le_bytes())?;
+        self.write(&(data.len() as u64).to_le_bytes())?;
+        self.write(data)
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.file.is_none() && self.memory.len() + data.len() > SPILL_THRESHOLD {
+            let mut file = BufWriter::new(TempFile::create()?);
+            file.write_all(&std::mem::take(&mut self.memory))?;
+            self.file = Some(file);
+        }
+
+        match &mut self.file {
+            Some(file) => file.write_all(data),
+            None => {
+                self.memory.extend_from_slice(data);
+                Ok(())
+            }
+        }
+    }
+
+    pub(super) fn finish(self) -> io::Result<Storage> {
+        match self.file {
+            Some(file) => Ok(Storage::File(
+                file.into_inner().map_err(|e| e.into_error())?,
+            )),
+            None => Ok(Storage::Memory(self.memory)),
+        }
+    }
+}
+
+/// A file in the temporary directory that is deleted when it is dropped
+pub(super) struct TempFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create() -> io::Result<TempFile> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("textractor-scan-{}-{id}.bin", std::process::id()));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(TempFile { file, path })
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,282 @@
+use std::ops::Range;
+
+use crate::{
+    memory::{MemorySource, RemoteAddress, Result},
+    text::{self, Decoded, Encoding},
+};
+
+/// The longest a character may be in any of the encodings
+const MAX_CHAR_SIZE: usize = 4;
+
+/// A run of text found in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundString {
+    pub address: RemoteAddress,
+    pub encoding: Encoding,
+    pub text: String,
+}
+
+/// Finds runs of text in the readable memory of a [MemorySource], like `strings` does for files.
+///
+/// Runs are only reported in encodings that [extend ASCII](Encoding::extends_ascii) if they contain
+/// a character outside of ASCII, since plain ASCII text would otherwise show up once for every
+/// encoding. By default, the encodings are the ones most games use for their text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringScan {
+    pub encodings: Vec<Encoding>,
+    /// The number of characters a run needs to be reported
+    pub min_length: usize,
+    /// The number of characters after which a run is split
+    pub max_length: usize,
+}
+
+impl Default for StringScan {
+    fn default() -> StringScan {
+        StringScan {
+            encodings: vec![
+                Encoding::Ascii,
+                Encoding::Utf8,
+                Encoding::Utf16Le,
+                Encoding::ShiftJis,
+            ],
+            min_length: 4,
+            max_length: 4096,
+        }
+    }
+}
+
+impl StringScan {
+    /// Returns the text in all readable memory, ordered by encoding and then address
+    pub fn scan<S: MemorySource + ?Sized>(&self, source: &S) -> Result<Vec<FoundString>> {
+        self.scan_range(source, RemoteAddress::NULL..RemoteAddress::new(usize::MAX))
+    }
+
+    /// Returns the text in the readable memory of `range`
+    pub fn scan_range<S: MemorySource + ?Sized>(
+        &self,
+        source: &S,
+        range: Range<RemoteAddress>,
+    ) -> Result<Vec<FoundString>> {
+        let mut finders = self
+            .encodings
+            .iter()
+            .map(|&encoding| RunFinder::new(self, encoding))
+            .collect::<Vec<_>>();
+
+        super::for_each_chunk(source, range, MAX_CHAR_SIZE - 1, |address, data| {
+            for finder in &mut finders {
+                finder.feed(address, data);
+            }
+
+            Ok(())
+        })?;
+
+        Ok(finders
+            .into_iter()
+            .flat_map(|mut finder| {
+                finder.finish();
+                finder.found
+            })
+            .collect())
+    }
+}
+
+/// Follows the runs of text of one encoding through consecutive chunks of memory
+struct RunFinder<'a> {
+    scan: &'a StringScan,
+    encoding: Encoding,
+    /// Everything before this address has been looked at
+    position: RemoteAddress,
+    start: RemoteAddress,
+    text: String,
+    chars: usize,
+    found: Vec<FoundString>,
+}
+
+impl<'a> RunFinder<'a> {
+    fn new(scan: &'a StringScan, encoding: Encoding) -> RunFinder<'a> {
+        RunFinder {
+            scan,
+            encoding,
+            position: RemoteAddress::NULL,
+            start: RemoteAddress::NULL,
+            text: String::new(),
+            chars: 0,
+            found: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, address: RemoteAddress, data: &[u8]) {
+        // Chunks either overlap the previous one, or start somewhere new
+        let mut offset = match self.position.get().checked_sub(address.get()) {
+            Some(offset) if offset <= data.len() => offset,
+            _ => {
+                self.finish();
+                // Keep characters aligned to code units, wherever the chunk starts
+                let unit_size = self.encoding.unit_size();
+                (unit_size - address.get() % unit_size) % unit_size
+            }
+        };
+
+        while offset < data.len() {
+            match self.encoding.decode_char(&data[offset..]) {
+                Decoded::Char(c, len) if text::is_text(c) => {
+                    if self.chars == 0 {
+                        self.start = address + offset;
+                    }
+
+                    self.text.push(c);
+                    self.chars += 1;
+                    offset += len;
+
+                    if self.chars >= self.scan.max_length {
+                        self.finish();
+                    }
+                }
+                // Pick up where we left off in the next chunk, which starts a little earlier
+                Decoded::Incomplete => break,
+                _ => {
+                    self.finish();
+                    offset += self.encoding.unit_size();
+                }
+            }
+        }
+
+        self.position = address + offset;
+    }
+
+    /// Ends the current run, keeping it if it looks like text
+    fn finish(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let chars = std::mem::replace(&mut self.chars, 0);
+
+        let mistaken_for_ascii = self.encoding.extends_ascii() && text.is_ascii();
+        if chars == 0 || chars < self.scan.min_length || mistaken_for_ascii {
+            return;
+        }
+
+        self.found.push(FoundString {
+            address: self.start,
+            encoding: self.encoding,
+            text,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memory::{mock::MockProcess, Protection},
+        scan::CHUNK_SIZE,
+    };
+
+    const BASE: usize = 0x10000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    fn scan(encoding: Encoding) -> StringScan {
+        StringScan {
+            encodings: vec![encoding],
+            ..StringScan::default()
+        }
+    }
+
+    fn found(scan: &StringScan, process: &MockProcess) -> Vec<(usize, String)> {
+        scan.scan(process)
+            .unwrap()
+            .into_iter()
+            .map(|found| (found.address.get(), found.text))
+            .collect()
+    }
+
+    fn write(process: &MockProcess, address: usize, encoding: Encoding, text: &str) {
+        let bytes = encoding.encode(text).unwrap();
+        process.write(addr(address), &bytes).unwrap();
+    }
+
+    #[test]
+    fn across_chunks() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 2 * CHUNK_SIZE, Protection::READ_WRITE);
+
+        let text = "チャンクをまたぐテキスト";
+        for (encoding, offset) in [
+            (Encoding::ShiftJis, 7),
+            (Encoding::Utf8, 5),
+            (Encoding::Utf16Le, 6),
+        ] {
+            // Starting just before the end of the first chunk, and with a character cut in two
+            let address = BASE + CHUNK_SIZE - offset;
+            write(&process, address, encoding, text);
+
+            let scan = scan(encoding);
+            assert_eq!(found(&scan, &process), [(address, text.to_string())]);
+
+            process.write(addr(address), &[0; 64]).unwrap();
+        }
+    }
+
+    #[test]
+    fn unaligned_utf16() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 0x1000, Protection::READ_WRITE);
+        write(&process, BASE + 0x101, Encoding::Utf16Le, "Odd address");
+        write(&process, BASE + 0x200, Encoding::Utf16Le, "Even address");
+
+        // Characters are aligned to code units, so text at an odd address is misread
+        let scan = scan(Encoding::Utf16Le);
+        let found = found(&scan, &process);
+        assert!(found.iter().all(|(_, text)| text != "Odd address"));
+        assert!(found.contains(&(BASE + 0x200, "Even address".to_string())));
+
+        // The alignment does not depend on where the scan starts
+        for start in [BASE + 1, BASE + 0x181, BASE + 0x1FF] {
+            let range = addr(start)..addr(BASE + 0x1000);
+            let found = scan.scan_range(&process, range).unwrap();
+            let expected = scan
+                .scan(&process)
+                .unwrap()
+                .into_iter()
+                .filter(|found| found.address.get() >= start)
+                .collect::<Vec<_>>();
+            assert_eq!(found, expected, "starting at {start:#x}");
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), 0x1000, Protection::READ_WRITE);
+        // Lengths count characters rather than bytes
+        write(&process, BASE, Encoding::ShiftJis, "テキス");
+        write(&process, BASE + 0x10, Encoding::ShiftJis, "テキスト");
+        write(&process, BASE + 0x20, Encoding::ShiftJis, "ab");
+        // ASCII is left to the ASCII scan
+        write(&process, BASE + 0x30, Encoding::ShiftJis, "ASCII only");
+        write(&process, BASE + 0x40, Encoding::ShiftJis, "0123456789文字");
+
+        let mut scan = scan(Encoding::ShiftJis);
+        assert_eq!(
+            found(&scan, &process),
+            [
+                (BASE + 0x10, "テキスト".to_string()),
+                (BASE + 0x40, "0123456789文字".to_string())
+            ]
+        );
+
+        scan.min_length = 2;
+        scan.max_length = 6;
+        assert_eq!(
+            found(&scan, &process),
+            [
+                (BASE, "テキス".to_string()),
+                (BASE + 0x10, "テキスト".to_string()),
+                // Split after six characters, leaving an ASCII half that is dropped
+                (BASE + 0x46, "6789文字".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::VecDeque, io::Read};
+
+use super::{
+    storage::{read_block, Storage, StorageWriter},
+    Pattern,
+};
+use crate::{
+    memory::{MemorySource, RemoteAddress, Result},
+    text::Encoding,
+};
+
+/// Text that appeared in memory since the previous snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextMatch {
+    pub address: RemoteAddress,
+    pub encoding: Encoding,
+}
+
+/// Finds the buffers that a line of text was just written to.
+///
+/// Take a [snapshot](Self::snapshot), advance the dialogue, and then [find](Self::find) the line
+/// that is now on screen. Only places that did not already contain the text at the time of the
+/// snapshot are reported, which leaves out old copies of the same line, and every find takes a
+/// new snapshot so the next line can be found right away.
+pub struct TextDiffScan {
+    pub encodings: Vec<Encoding>,
+    snapshot: Option<Storage>,
+}
+
+impl Default for TextDiffScan {
+    fn default() -> TextDiffScan {
+        TextDiffScan {
+            encodings: Encoding::ALL.to_vec(),
+            snapshot: None,
+        }
+    }
+}
+
+impl TextDiffScan {
+    /// Remembers the current contents of the readable memory
+    pub fn snapshot<S: MemorySource + ?Sized>(&mut self, source: &S) -> Result<()> {
+        let mut writer = SnapshotWriter::default();
+
+        super::for_each_chunk(source, full_range(), 0, |address, data| {
+            writer.write(address, data)
+        })?;
+
+        self.snapshot = Some(writer.finish()?);
+
+        Ok(())
+    }
+
+    /// Returns where `text`, or a part of a longer line, is stored in any of the encodings but
+    /// was not at the time of the last snapshot. Without a snapshot, every match is returned.
+    ///
+    /// Bytes that spell the text in several encodings, like ASCII text in UTF-8 or Shift-JIS, are
+    /// reported once with the first of them.
+    pub fn find<S: MemorySource + ?Sized>(
+        &mut self,
+        source: &S,
+        text: &str,
+    ) -> Result<Vec<TextMatch>> {
+        let patterns = self
+            .encodings
+            .iter()
+            .filter_map(|&encoding| {
+                let bytes = encoding.encode(text)?;
+                let pattern = Pattern::new(bytes.into_iter().map(Some).collect()).ok()?;
+
+                Some((encoding, pattern))
+            })
+            .collect::<Vec<_>>();
+
+        let Some(longest) = patterns.iter().map(|(_, pattern)| pattern.len()).max() else {
+            return Ok(Vec::new());
+        };
+
+        // Matches in the overlap of two chunks may start before those of the previous chunk
+        let mut previous = match &self.snapshot {
+            Some(snapshot) => Some(SnapshotReader::new(snapshot.reader()?, longest)),
+            None => None,
+        };
+        let mut writer = SnapshotWriter::default();
+        let mut found = Vec::new();
+
+        super::for_each_chunk(source, full_range(), longest - 1, |address, data| {
+            // Shorter patterns may be found in the overlap with the previous chunk a second time
+            let seen = writer.end;
+            writer.write(address, data)?;
+
+            let mut matches = Vec::new();
+            for (encoding, pattern) in &patterns {
+                for offset in pattern.find_iter(data) {
+                    let start = address + offset;
+                    let end = start + pattern.len();
+
+                    if start.get() % encoding.unit_size() == 0 && end > seen {
+                        matches.push((start, *encoding, &data[offset..offset + pattern.len()]));
+                    }
+                }
+            }
+            // The sort is stable, so duplicates keep the encoding that comes first
+            matches.sort_by_key(|(start, _, bytes)| (*start, bytes.len()));
+            matches.dedup_by_key(|(start, _, bytes)| (*start, bytes.len()));
+
+            for (address, encoding, bytes) in matches {
+                let existed = match &mut previous {
+                    Some(previous) => previous.contains(address, bytes)?,
+                    None => false,
+                };
+
+                if !existed {
+                    found.push(TextMatch { address, encoding });
+                }
+            }
+
+            Ok(())
+        })?;
+
+        drop(previous);
+        self.snapshot = Some(writer.finish()?);
+
+        Ok(found)
+    }
+
+    /// Forgets the snapshot, so that the next find returns every match
+    pub fn clear(&mut self) {
+        self.snapshot = None;
+    }
+}
+
+fn full_range() -> std::ops::Range<RemoteAddress> {
+    RemoteAddress::NULL..RemoteAddress::new(usize::MAX)
+}
+
+/// Stores overlapping chunks of memory as blocks that do not overlap
+#[derive(Default)]
+struct SnapshotWriter {
+    storage: StorageWriter,
+    /// Everything before this address has been stored
+    end: RemoteAddress,
+}
+
+impl SnapshotWriter {
+    fn write(&mut self, address: RemoteAddress, data: &[u8]) -> Result<()> {
+        let skip = self.end.get().saturating_sub(address.get()).min(data.len());
+
+        if skip < data.len() {
+            self.storage.write_block(address + skip, &data[skip..])?;
+            self.end = address + data.len();
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Storage> {
+        Ok(self.storage.finish()?)
+    }
+}
+
+/// Walks through the blocks of a snapshot in ascending order of address, keeping only the blocks
+/// near the latest address asked about in memory
+struct SnapshotReader<R: Read> {
+    reader: R,
+    blocks: VecDeque<(RemoteAddress, Vec<u8>)>,
+    exhausted: bool,
+    /// How far before the latest address later questions may start
+    lookback: usize,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    fn new(reader: R, lookback: usize) -> SnapshotReader<R> {
+        SnapshotReader {
+            reader,
+            blocks: VecDeque::new(),
+            exhausted: false,
+            lookback,
+        }
+    }
+
+    /// Drops the blocks that end before `address`, which may not be asked about anymore
+    fn discard_before(&mut self, address: RemoteAddress) {
+        while self
+            .blocks
+            .front()
+            .is_some_and(|(start, data)| *start + data.len() <= address)
+        {
+            self.blocks.pop_front();
+        }
+    }
+
+    /// Returns whether the snapshot holds `bytes` at `address`
+    fn contains(&mut self, address: RemoteAddress, bytes: &[u8]) -> Result<bool> {
+        let end = address + bytes.len();
+        let keep = RemoteAddress::new(address.get().saturating_sub(self.lookback));
+        self.discard_before(keep);
+
+        while !self.exhausted
+            && self
+                .blocks
+                .back()
+                .is_none_or(|(start, data)| *start + data.len() < end)
+        {
+            match read_block(&mut self.reader)? {
+                Some(block) => {
+                    self.blocks.push_back(block);
+                    // Skipping over a long stretch without matches must not load all of it
+                    self.discard_before(keep);
+                }
+                None => self.exhausted = true,
+            }
+        }
+
+        let mut cursor = address;
+        for (start, data) in &self.blocks {
+            let block_end = *start + data.len();
+            if block_end <= cursor {
+                continue;
+            }
+            // There is a gap, so the memory was not readable back then
+            if *start > cursor {
+                return Ok(false);
+            }
+
+            let from = cursor - *start;
+            let len = block_end.min(end) - cursor;
+            let expected = &bytes[cursor - address..cursor - address + len];
+
+            if data[from..from + len] != *expected {
+                return Ok(false);
+            }
+
+            cursor += len;
+            if cursor == end {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{mock::MockProcess, Protection};
+
+    fn process() -> MockProcess {
+        let mut process = MockProcess::new();
+        process.map_zeroed(RemoteAddress::new(0x10000), 0x4000, Protection::READ_WRITE);
+        process
+    }
+
+    #[test]
+    fn matches_are_reported_once() {
+        let process = process();
+        process
+            .write(RemoteAddress::new(0x10100), b"Hello there")
+            .unwrap();
+        process
+            .write(
+                RemoteAddress::new(0x12000),
+                &Encoding::Utf16Le.encode("Hello there").unwrap(),
+            )
+            .unwrap();
+
+        let found = TextDiffScan::default()
+            .find(&process, "Hello there")
+            .unwrap();
+
+        assert_eq!(
+            found,
+            [
+                TextMatch {
+                    address: RemoteAddress::new(0x10100),
+                    encoding: Encoding::Ascii,
+                },
+                TextMatch {
+                    address: RemoteAddress::new(0x12000),
+                    encoding: Encoding::Utf16Le,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_new_text_is_reported() {
+        let process = process();
+        let mut scan = TextDiffScan {
+            encodings: vec![Encoding::Utf8],
+            ..Default::default()
+        };
+        process
+            .write(RemoteAddress::new(0x10100), "こんにちは".as_bytes())
+            .unwrap();
+
+        scan.snapshot(&process).unwrap();
+        process
+            .write(RemoteAddress::new(0x13F00), "こんにちは".as_bytes())
+            .unwrap();
+
+        assert_eq!(
+            scan.find(&process, "こんにちは").unwrap(),
+            [TextMatch {
+                address: RemoteAddress::new(0x13F00),
+                encoding: Encoding::Utf8,
+            }]
+        );
+        // The find took a new snapshot
+        assert!(scan.find(&process, "こんにちは").unwrap().is_empty());
+    }
+
+    #[test]
+    fn reader_keeps_only_nearby_blocks() {
+        let mut writer = SnapshotWriter::default();
+        for i in 0..256 {
+            writer
+                .write(RemoteAddress::new(i * 0x1000), &[i as u8; 0x1000])
+                .unwrap();
+        }
+        let storage = writer.finish().unwrap();
+        let mut reader = SnapshotReader::new(storage.reader().unwrap(), 16);
+
+        assert!(reader
+            .contains(RemoteAddress::new(0xFAFF8), &[0xFA; 8])
+            .unwrap());
+        assert_eq!(reader.blocks.len(), 1);
+
+        // Across the boundary of two blocks
+        assert!(reader
+            .contains(RemoteAddress::new(0xFAFFC), &[0xFA, 0xFA, 0xFA, 0xFA, 0xFB])
+            .unwrap());
+        assert!(!reader
+            .contains(RemoteAddress::new(0xFB000), &[0xFA])
+            .unwrap());
+        assert!(reader.blocks.len() <= 2);
+
+        assert!(!reader.contains(RemoteAddress::new(0x100000), &[0]).unwrap());
+    }
+}
@@ -0,0 +1,720 @@
+use std::{cmp::Ordering, fmt, io};
+
+use thiserror::Error;
+
+use super::{
+    storage::{read_block, read_records, Storage, StorageWriter},
+    Pattern,
+};
+use crate::{
+    memory::{self, cache::CachedReader, MemorySource, RemoteAddress},
+    text::Encoding,
+};
+
+/// The number of results read from the target at once during a next scan
+const BATCH_SIZE: usize = 4096;
+
+#[derive(Error, Debug)]
+pub enum ValueScanError {
+    #[error("Failed to access the memory or the stored results")]
+    Memory(#[from] memory::Error),
+    #[error("The value is not of type {0}")]
+    TypeMismatch(ValueType),
+    #[error("The value is empty")]
+    Empty,
+    #[error("The alignment must be at least 1")]
+    InvalidAlignment,
+    #[error("Text can only be compared for equality")]
+    Unordered,
+    #[error("The text can not be represented in {0}")]
+    Unencodable(Encoding),
+}
+
+impl From<io::Error> for ValueScanError {
+    fn from(error: io::Error) -> ValueScanError {
+        ValueScanError::Memory(error.into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ValueScanError>;
+
+/// The type of the values a [ValueScan] looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// Text taking up `size` bytes in an encoding
+    Text {
+        encoding: Encoding,
+        size: usize,
+    },
+}
+
+impl ValueType {
+    /// Returns the number of bytes a value takes up
+    pub fn size(self) -> usize {
+        match self {
+            ValueType::U8 | ValueType::I8 => 1,
+            ValueType::U16 | ValueType::I16 => 2,
+            ValueType::U32 | ValueType::I32 | ValueType::F32 => 4,
+            ValueType::U64 | ValueType::I64 | ValueType::F64 => 8,
+            ValueType::Text { size, .. } => size,
+        }
+    }
+
+    /// Returns the natural alignment of values, which scans assume unless told otherwise
+    pub fn alignment(self) -> usize {
+        match self {
+            ValueType::Text { encoding, .. } => encoding.unit_size(),
+            _ => self.size(),
+        }
+    }
+
+    /// Decodes a value from bytes in the byte order of the host
+    pub fn decode(self, bytes: &[u8]) -> Value {
+        fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+            bytes[..N].try_into().unwrap()
+        }
+
+        match self {
+            ValueType::U8 => Value::U8(bytes[0]),
+            ValueType::U16 => Value::U16(u16::from_ne_bytes(array(bytes))),
+            ValueType::U32 => Value::U32(u32::from_ne_bytes(array(bytes))),
+            ValueType::U64 => Value::U64(u64::from_ne_bytes(array(bytes))),
+            ValueType::I8 => Value::I8(bytes[0] as i8),
+            ValueType::I16 => Value::I16(i16::from_ne_bytes(array(bytes))),
+            ValueType::I32 => Value::I32(i32::from_ne_bytes(array(bytes))),
+            ValueType::I64 => Value::I64(i64::from_ne_bytes(array(bytes))),
+            ValueType::F32 => Value::F32(f32::from_ne_bytes(array(bytes))),
+            ValueType::F64 => Value::F64(f64::from_ne_bytes(array(bytes))),
+            ValueType::Text { encoding, size } => {
+                Value::Text(encoding.decode_lossy(&bytes[..size]), encoding)
+            }
+        }
+    }
+
+    /// Compares two encoded values, returning `None` if they can not be ordered
+    fn compare(self, a: &[u8], b: &[u8]) -> Option<Ordering> {
+        if !self.is_ordered() {
+            return (a[..self.size()] == b[..self.size()]).then_some(Ordering::Equal);
+        }
+
+        match (self.decode(a), self.decode(b)) {
+            (Value::U8(a), Value::U8(b)) => Some(a.cmp(&b)),
+            (Value::U16(a), Value::U16(b)) => Some(a.cmp(&b)),
+            (Value::U32(a), Value::U32(b)) => Some(a.cmp(&b)),
+            (Value::U64(a), Value::U64(b)) => Some(a.cmp(&b)),
+            (Value::I8(a), Value::I8(b)) => Some(a.cmp(&b)),
+            (Value::I16(a), Value::I16(b)) => Some(a.cmp(&b)),
+            (Value::I32(a), Value::I32(b)) => Some(a.cmp(&b)),
+            (Value::I64(a), Value::I64(b)) => Some(a.cmp(&b)),
+            (Value::F32(a), Value::F32(b)) => a.partial_cmp(&b),
+            (Value::F64(a), Value::F64(b)) => a.partial_cmp(&b),
+            _ => None,
+        }
+    }
+
+    fn is_ordered(self) -> bool {
+        !matches!(self, ValueType::Text { .. })
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueType::U8 => f.write_str("u8"),
+            ValueType::U16 => f.write_str("u16"),
+            ValueType::U32 => f.write_str("u32"),
+            ValueType::U64 => f.write_str("u64"),
+            ValueType::I8 => f.write_str("i8"),
+            ValueType::I16 => f.write_str("i16"),
+            ValueType::I32 => f.write_str("i32"),
+            ValueType::I64 => f.write_str("i64"),
+            ValueType::F32 => f.write_str("f32"),
+            ValueType::F64 => f.write_str("f64"),
+            ValueType::Text { encoding, size } => write!(f, "{encoding} text of {size} bytes"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Text(String, Encoding),
+}
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::U8(_) => ValueType::U8,
+            Value::U16(_) => ValueType::U16,
+            Value::U32(_) => ValueType::U32,
+            Value::U64(_) => ValueType::U64,
+            Value::I8(_) => ValueType::I8,
+            Value::I16(_) => ValueType::I16,
+            Value::I32(_) => ValueType::I32,
+            Value::I64(_) => ValueType::I64,
+            Value::F32(_) => ValueType::F32,
+            Value::F64(_) => ValueType::F64,
+            Value::Text(text, encoding) => ValueType::Text {
+                encoding: *encoding,
+                // Unencodable text is rejected by `encode`, so the size does not matter
+                size: encoding.encode(text).map_or(0, |bytes| bytes.len()),
+            },
+        }
+    }
+
+    /// Encodes the value in the byte order of the host
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(match self {
+            Value::U8(value) => value.to_ne_bytes().to_vec(),
+            Value::U16(value) => value.to_ne_bytes().to_vec(),
+            Value::U32(value) => value.to_ne_bytes().to_vec(),
+            Value::U64(value) => value.to_ne_bytes().to_vec(),
+            Value::I8(value) => value.to_ne_bytes().to_vec(),
+            Value::I16(value) => value.to_ne_bytes().to_vec(),
+            Value::I32(value) => value.to_ne_bytes().to_vec(),
+            Value::I64(value) => value.to_ne_bytes().to_vec(),
+            Value::F32(value) => value.to_ne_bytes().to_vec(),
+            Value::F64(value) => value.to_ne_bytes().to_vec(),
+            Value::Text(text, encoding) => encoding
+                .encode(text)
+                .ok_or(ValueScanError::Unencodable(*encoding))?,
+        })
+    }
+
+    /// Encodes the value, making sure it has the given type
+    fn encode_as(&self, value_type: ValueType) -> Result<Vec<u8>> {
+        if self.value_type() != value_type {
+            return Err(ValueScanError::TypeMismatch(value_type));
+        }
+
+        self.encode()
+    }
+}
+
+/// How the first scan picks its results
+#[derive(Debug, Clone, PartialEq)]
+pub enum FirstScan {
+    /// Values that are exactly the same, byte for byte. Floats that are the result of a
+    /// calculation are better found with a range.
+    Exact(Value),
+    /// Values between two bounds, inclusive
+    Range(Value, Value),
+    /// Every value, to be narrowed down by comparing it to itself in the next scans
+    Unknown(ValueType),
+}
+
+/// How a next scan narrows the results, by comparing the current value to the one from the
+/// previous scan
+#[derive(Debug, Clone, PartialEq)]
+pub enum NextScan {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+    Equals(Value),
+}
+
+/// An address that survived every scan so far, and its value as of the last scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResult {
+    pub address: RemoteAddress,
+    pub value: Value,
+}
+
+/// A Cheat Engine style scan session that narrows down where a value is stored.
+///
+/// The results are kept in memory while they are small and moved to a temporary file once they
+/// are not. A scan for an unknown initial value only stores a copy of the readable memory, and
+/// the first next scan turns it into a list of addresses.
+pub struct ValueScan {
+    value_type: ValueType,
+    /// Only addresses that are a multiple of this are results
+    alignment: usize,
+    results: Results,
+    count: u64,
+}
+
+enum Results {
+    /// Blocks of `[address: u64][size: u64][data]`, where every aligned address is a result
+    Snapshot(Storage),
+    /// Records of `[address: u64][value]`
+    List(Storage),
+}
+
+impl ValueScan {
+    /// Scans all readable memory of `source` for values at their natural
+    /// [alignment](ValueType::alignment)
+    pub fn first<S: MemorySource + ?Sized>(source: &S, scan: FirstScan) -> Result<ValueScan> {
+        let alignment = scan_type(&scan).alignment();
+        ValueScan::first_aligned(source, scan, alignment)
+    }
+
+    /// Scans all readable memory of `source` for values at addresses that are a multiple of
+    /// `alignment`, such as 8 byte values that a 32-bit program only aligns to 4 bytes
+    pub fn first_aligned<S: MemorySource + ?Sized>(
+        source: &S,
+        scan: FirstScan,
+        alignment: usize,
+    ) -> Result<ValueScan> {
+        let value_type = scan_type(&scan);
+        let size = value_type.size();
+        let range = RemoteAddress::NULL..RemoteAddress::new(usize::MAX);
+        let unknown = matches!(scan, FirstScan::Unknown(_));
+
+        if size == 0 {
+            return Err(ValueScanError::Empty);
+        }
+        if alignment == 0 {
+            return Err(ValueScanError::InvalidAlignment);
+        }
+
+        let mut writer = StorageWriter::default();
+        let mut count = 0;
+
+        match scan {
+            FirstScan::Exact(value) => {
+                let bytes = value.encode()?;
+                let pattern = Pattern::new(bytes.into_iter().map(Some).collect())
+                    .map_err(|_| ValueScanError::TypeMismatch(value_type))?;
+
+                super::for_each_chunk(source, range, size - 1, |address, data| {
+                    for offset in pattern.find_iter(data) {
+                        let address = address + offset;
+
+                        if address.get() % alignment == 0 {
+                            writer.write_record(address, &data[offset..offset + size])?;
+                            count += 1;
+                        }
+                    }
+
+                    Ok(())
+                })?;
+            }
+            FirstScan::Range(min, max) => {
+                if !value_type.is_ordered() {
+                    return Err(ValueScanError::Unordered);
+                }
+
+                let min = min.encode_as(value_type)?;
+                let max = max.encode_as(value_type)?;
+
+                super::for_each_chunk(source, range, size - 1, |address, data| {
+                    for offset in aligned_offsets(address, data.len(), value_type, alignment) {
+                        let value = &data[offset..offset + size];
+
+                        let above = matches!(
+                            value_type.compare(value, &min),
+                            Some(Ordering::Greater | Ordering::Equal)
+                        );
+                        let below = matches!(
+                            value_type.compare(value, &max),
+                            Some(Ordering::Less | Ordering::Equal)
+                        );
+
+                        if above && below {
+                            writer.write_record(address + offset, value)?;
+                            count += 1;
+                        }
+                    }
+
+                    Ok(())
+                })?;
+            }
+            FirstScan::Unknown(_) => {
+                super::for_each_chunk(source, range, size - 1, |address, data| {
+                    writer.write_block(address, data)?;
+                    count +=
+                        aligned_offsets(address, data.len(), value_type, alignment).count() as u64;
+
+                    Ok(())
+                })?;
+            }
+        }
+
+        let storage = writer.finish()?;
+        let results = match unknown {
+            true => Results::Snapshot(storage),
+            false => Results::List(storage),
+        };
+
+        Ok(ValueScan {
+            value_type,
+            alignment,
+            results,
+            count,
+        })
+    }
+
+    /// Narrows the results down to the values that pass `scan`
+    pub fn next<S: MemorySource + ?Sized>(&mut self, source: &S, scan: NextScan) -> Result<()> {
+        let value_type = self.value_type;
+        let size = value_type.size();
+
+        let target = match &scan {
+            NextScan::Increased | NextScan::Decreased if !value_type.is_ordered() => {
+                return Err(ValueScanError::Unordered);
+            }
+            NextScan::Equals(value) => Some(value.encode_as(value_type)?),
+            _ => None,
+        };
+
+        let keep = |current: &[u8], previous: &[u8]| {
+            let ordering = match &target {
+                Some(target) => value_type.compare(current, target),
+                None => value_type.compare(current, previous),
+            };
+
+            match scan {
+                NextScan::Changed => ordering != Some(Ordering::Equal),
+                NextScan::Unchanged | NextScan::Equals(_) => ordering == Some(Ordering::Equal),
+                NextScan::Increased => ordering == Some(Ordering::Greater),
+                NextScan::Decreased => ordering == Some(Ordering::Less),
+            }
+        };
+
+        let mut writer = StorageWriter::default();
+        let mut count = 0;
+
+        match &self.results {
+            Results::Snapshot(storage) => {
+                let mut reader = storage.reader()?;
+                let mut current = Vec::new();
+
+                while let Some((address, previous)) = read_block(&mut reader)? {
+                    current.resize(previous.len(), 0);
+                    let read = source.read(address, &mut current).unwrap_or(0);
+
+                    for offset in aligned_offsets(address, read, value_type, self.alignment) {
+                        let current = &current[offset..offset + size];
+
+                        if keep(current, &previous[offset..offset + size]) {
+                            writer.write_record(address + offset, current)?;
+                            count += 1;
+                        }
+                    }
+                }
+            }
+            Results::List(storage) => {
+                let mut reader = storage.reader()?;
+                let cache = CachedReader::new(source);
+
+                loop {
+                    let batch = read_records(&mut reader, size, BATCH_SIZE)?;
+                    if batch.is_empty() {
+                        break;
+                    }
+
+                    let mut current = vec![0; batch.len() * size];
+                    let mut requests = batch
+                        .iter()
+                        .map(|(address, _)| *address)
+                        .zip(current.chunks_exact_mut(size))
+                        .collect::<Vec<_>>();
+                    let reads = cache.read_many(&mut requests);
+
+                    for (((address, value), previous), read) in
+                        requests.iter().zip(&batch).zip(reads)
+                    {
+                        if read.is_ok_and(|read| read == size) && keep(value, &previous.1) {
+                            writer.write_record(*address, value)?;
+                            count += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.results = Results::List(writer.finish()?);
+        self.count = count;
+
+        Ok(())
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    pub fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    /// Returns the number of results
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns up to `limit` results in ascending order of address, with their values as of the
+    /// last scan
+    pub fn results(&self, limit: usize) -> Result<Vec<ScanResult>> {
+        let value_type = self.value_type;
+        let size = value_type.size();
+        let mut results = Vec::new();
+
+        match &self.results {
+            Results::Snapshot(storage) => {
+                let mut reader = storage.reader()?;
+
+                while let Some((address, data)) = read_block(&mut reader)? {
+                    for offset in aligned_offsets(address, data.len(), value_type, self.alignment) {
+                        if results.len() == limit {
+                            return Ok(results);
+                        }
+
+                        results.push(ScanResult {
+                            address: address + offset,
+                            value: value_type.decode(&data[offset..]),
+                        });
+                    }
+                }
+            }
+            Results::List(storage) => {
+                let mut reader = storage.reader()?;
+
+                results.extend(read_records(&mut reader, size, limit)?.into_iter().map(
+                    |(address, value)| ScanResult {
+                        address,
+                        value: value_type.decode(&value),
+                    },
+                ));
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+/// Returns the type of the values a first scan looks for
+fn scan_type(scan: &FirstScan) -> ValueType {
+    match scan {
+        FirstScan::Exact(value) | FirstScan::Range(value, _) => value.value_type(),
+        FirstScan::Unknown(value_type) => *value_type,
+    }
+}
+
+/// Returns the offsets into `len` bytes at `address` where a whole aligned value fits
+fn aligned_offsets(
+    address: RemoteAddress,
+    len: usize,
+    value_type: ValueType,
+    alignment: usize,
+) -> impl Iterator<Item = usize> {
+    let first = (alignment - address.get() % alignment) % alignment;
+    let end = (len + 1).saturating_sub(value_type.size());
+
+    (first..end).step_by(alignment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::{mock::MockProcess, Protection};
+
+    const BASE: usize = 0x10000;
+
+    fn addr(address: usize) -> RemoteAddress {
+        RemoteAddress::new(address)
+    }
+
+    fn process(size: usize) -> MockProcess {
+        let mut process = MockProcess::new();
+        process.map_zeroed(addr(BASE), size, Protection::READ_WRITE);
+        process
+    }
+
+    fn addresses(scan: &ValueScan) -> Vec<usize> {
+        scan.results(usize::MAX)
+            .unwrap()
+            .iter()
+            .map(|result| result.address.get())
+            .collect()
+    }
+
+    #[test]
+    fn first_scan() {
+        let mut process = process(0x2000);
+        // Memory that can not be read is skipped
+        let unreadable = 1234u32.to_ne_bytes().repeat(0x400);
+        process.map(addr(BASE + 0x3000), unreadable, Protection::NONE);
+        for address in [BASE + 0x10, BASE + 0x1FFC] {
+            process.write_u32(addr(address), 1234).unwrap();
+        }
+        // Not aligned, so not a result
+        process.write_u32(addr(BASE + 0x21), 1234).unwrap();
+        process.write_u32(addr(BASE + 0x40), 1240).unwrap();
+
+        let scan = ValueScan::first(&process, FirstScan::Exact(Value::U32(1234))).unwrap();
+        assert_eq!(addresses(&scan), [BASE + 0x10, BASE + 0x1FFC]);
+        assert_eq!(scan.len(), 2);
+        assert_eq!(scan.alignment(), 4);
+
+        let range = FirstScan::Range(Value::U32(1000), Value::U32(1239));
+        let scan = ValueScan::first(&process, range).unwrap();
+        assert_eq!(addresses(&scan), [BASE + 0x10, BASE + 0x1FFC]);
+
+        let text = Value::Text("テキスト".to_string(), Encoding::Utf16Le);
+        process
+            .write(addr(BASE + 0x102), &text.encode().unwrap())
+            .unwrap();
+        let scan = ValueScan::first(&process, FirstScan::Exact(text.clone())).unwrap();
+        let results = scan.results(10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            (results[0].address, &results[0].value),
+            (addr(BASE + 0x102), &text)
+        );
+
+        assert!(matches!(
+            ValueScan::first(&process, FirstScan::Range(text.clone(), text)),
+            Err(ValueScanError::Unordered)
+        ));
+        assert!(matches!(
+            ValueScan::first(&process, FirstScan::Range(Value::U32(0), Value::U16(1))),
+            Err(ValueScanError::TypeMismatch(ValueType::U32))
+        ));
+    }
+
+    #[test]
+    fn alignment() {
+        let process = process(0x1000);
+        process.write_u64(addr(BASE + 0x14), u64::MAX - 1).unwrap();
+        let exact = || FirstScan::Exact(Value::U64(u64::MAX - 1));
+
+        let scan = ValueScan::first(&process, exact()).unwrap();
+        assert!(scan.is_empty());
+
+        // 32-bit programs only align 8 byte values to 4 bytes
+        let scan = ValueScan::first_aligned(&process, exact(), 4).unwrap();
+        assert_eq!(addresses(&scan), [BASE + 0x14]);
+
+        let scan = ValueScan::first_aligned(&process, FirstScan::Unknown(ValueType::U64), 4);
+        let mut scan = scan.unwrap();
+        assert_eq!(scan.len(), 0x1000 / 4 - 1);
+        scan.next(&process, NextScan::Equals(Value::U64(u64::MAX - 1)))
+            .unwrap();
+        assert_eq!(addresses(&scan), [BASE + 0x14]);
+
+        assert!(matches!(
+            ValueScan::first_aligned(&process, exact(), 0),
+            Err(ValueScanError::InvalidAlignment)
+        ));
+    }
+
+    #[test]
+    fn next_scans() {
+        let process = process(0x1000);
+        let values = [5i32, 5, 5, 5];
+        let address = |i: usize| BASE + 0x100 + i * 4;
+        for (i, value) in values.iter().enumerate() {
+            process.write_i32(addr(address(i)), *value).unwrap();
+        }
+
+        let mut scan = ValueScan::first(&process, FirstScan::Exact(Value::I32(5))).unwrap();
+        assert_eq!(scan.len(), 4);
+
+        // The first goes up, the second down and the third is cleared
+        process.write_i32(addr(address(0)), 6).unwrap();
+        process.write_i32(addr(address(1)), -1).unwrap();
+        process.write_i32(addr(address(2)), 0).unwrap();
+        scan.next(&process, NextScan::Changed).unwrap();
+        assert_eq!(addresses(&scan), [address(0), address(1), address(2)]);
+
+        scan.next(&process, NextScan::Unchanged).unwrap();
+        assert_eq!(scan.len(), 3);
+
+        let mut increased = ValueScan::first(&process, FirstScan::Unknown(ValueType::I32)).unwrap();
+        process.write_i32(addr(address(2)), 10).unwrap();
+        process.write_i32(addr(address(1)), -2).unwrap();
+        scan.next(&process, NextScan::Decreased).unwrap();
+        assert_eq!(addresses(&scan), [address(1)]);
+        assert_eq!(scan.results(1).unwrap()[0].value, Value::I32(-2));
+
+        increased.next(&process, NextScan::Increased).unwrap();
+        assert_eq!(addresses(&increased), [address(2)]);
+        increased
+            .next(&process, NextScan::Equals(Value::I32(11)))
+            .unwrap();
+        assert!(increased.is_empty());
+
+        assert!(matches!(
+            scan.next(&process, NextScan::Equals(Value::U32(1))),
+            Err(ValueScanError::TypeMismatch(ValueType::I32))
+        ));
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut process = process(0x1000);
+        process.map_zeroed(addr(BASE + 0x1000), 0x1000, Protection::NONE);
+        process.write_u16(addr(BASE + 0xFFE), 7).unwrap();
+
+        let mut scan = ValueScan::first(&process, FirstScan::Unknown(ValueType::U16)).unwrap();
+        assert!(matches!(scan.results, Results::Snapshot(_)));
+        assert_eq!(scan.len(), 0x800);
+
+        let results = scan.results(usize::MAX).unwrap();
+        assert_eq!(results.len(), 0x800);
+        assert_eq!(results[0x7FF].value, Value::U16(7));
+        assert_eq!(scan.results(3).unwrap().len(), 3);
+
+        // Memory that went away since the snapshot drops out
+        process.write_u16(addr(BASE + 0x10), 1).unwrap();
+        process.unmap(addr(BASE));
+        scan.next(&process, NextScan::Unchanged).unwrap();
+        assert!(scan.is_empty());
+    }
+
+    #[test]
+    fn results_spill_to_a_file() {
+        let process = process(0x4_0000);
+        for address in (BASE..BASE + 0x4_0000).step_by(4) {
+            process.write_u32(addr(address), 7).unwrap();
+        }
+
+        let mut scan = ValueScan::first(&process, FirstScan::Unknown(ValueType::U32)).unwrap();
+        assert!(matches!(scan.results, Results::Snapshot(Storage::File(_))));
+
+        scan.next(&process, NextScan::Unchanged).unwrap();
+        assert!(matches!(scan.results, Results::List(Storage::File(_))));
+        assert_eq!(scan.len(), 0x1_0000);
+
+        // Every other value changes
+        for address in (BASE..BASE + 0x4_0000).step_by(8) {
+            process.write_u32(addr(address), 8).unwrap();
+        }
+        scan.next(&process, NextScan::Increased).unwrap();
+        assert_eq!(scan.len(), 0x8000);
+        let results = scan.results(usize::MAX).unwrap();
+        assert_eq!(results.len(), 0x8000);
+        assert!(results.iter().enumerate().all(
+            |(i, result)| result.address == addr(BASE + i * 8) && result.value == Value::U32(8)
+        ));
+
+        let scan = ValueScan::first(&process, FirstScan::Exact(Value::U32(7))).unwrap();
+        assert!(matches!(scan.results, Results::List(Storage::File(_))));
+        assert_eq!(scan.len(), 0x8000);
+        assert_eq!(addresses(&scan)[..2], [BASE + 4, BASE + 12]);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{Decoded, Encoding};
+
+/// How well a buffer decodes in an encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub encoding: Encoding,
+    /// Between 0 and 1, where 1 means the whole buffer is plausible text
+    pub score: f64,
+}
+
+/// Scores a buffer against every encoding and returns the ones it could be in, best first.
+///
+/// Buffers read from a process are usually terminated and padded with NULs, so the text is taken
+/// to end at the first NUL character of each encoding. An encoding scores well if:
+///
+/// - its terminator is where the padding starts, as with ASCII text read as UTF-16LE
+/// - few bytes are invalid
+/// - the characters are common ones, such as kana, kanji and ASCII
+/// - it does not jump between scripts every other character, as misread CJK text does
+///
+/// Encodings that score the same are ranked by the order of [Encoding::ALL], so ASCII text
+/// comes out as ASCII rather than one of the encodings that extend it.
+pub fn detect(bytes: &[u8]) -> Vec<Candidate> {
+    let content = bytes.len() - bytes.iter().rev().take_while(|&&b| b == 0).count();
+    if content == 0 {
+        return Vec::new();
+    }
+
+    let mut candidates = Encoding::ALL
+        .iter()
+        .map(|&encoding| Candidate {
+            encoding,
+            score: score(encoding, bytes, content),
+        })
+        .filter(|candidate| candidate.score > 0.0)
+        .collect::<Vec<_>>();
+
+    // Stable, so that ties keep the order of `Encoding::ALL`
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    candidates
+}
+
+/// Scores the first `content` bytes of a buffer, which may be followed by padding
+fn score(encoding: Encoding, bytes: &[u8], content: usize) -> f64 {
+    // Trailing zeros may be half of the last code unit
+    let end = content
+        .next_multiple_of(encoding.unit_size())
+        .min(bytes.len());
+
+    let mut offset = 0;
+    let mut invalid = 0;
+    let mut chars = 0;
+    let mut weight = 0.0;
+    let mut switches = 0;
+    let mut last_ascii = None;
+
+    while offset < end {
+        match encoding.decode_char(&bytes[offset..end]) {
+            Decoded::Char('\0', _) => break,
+            Decoded::Char(c, len) => {
+                chars += 1;
+                weight += char_weight(c);
+                offset += len;
+
+                if last_ascii.is_some_and(|ascii| ascii != c.is_ascii()) {
+                    switches += 1;
+                }
+                last_ascii = Some(c.is_ascii());
+            }
+            Decoded::Incomplete | Decoded::Invalid => {
+                invalid += encoding.unit_size();
+                offset += encoding.unit_size();
+            }
+        }
+    }
+
+    if chars == 0 {
+        return 0.0;
+    }
+
+    let coverage = offset.min(content) as f64 / content as f64;
+    let validity = 1.0 - (invalid as f64 / offset as f64);
+    let plausibility = weight / chars as f64;
+    let consistency = 1.0 - 0.5 * (switches as f64 / chars as f64);
+
+    coverage * validity * plausibility * consistency
+}
+
+/// Returns how likely a character is to show up in the text of a game
+fn char_weight(c: char) -> f64 {
+    match c {
+        '\t' | '\n' | '\r' | ' '..='~' => 1.0,
+        // Half-width katakana, which is rare in text but is what Shift-JIS makes of most of the
+        // lead bytes of GBK and Big5
+        '\u{FF61}'..='\u{FF9F}' => 0.5,
+        // Hiragana, katakana, CJK punctuation and full-width forms
+        '\u{3000}'..='\u{30FF}' | '\u{FF00}'..='\u{FFEF}' => 1.0,
+        // Kanji and hanzi, which misread byte pairs also tend to land in
+        '\u{4E00}'..='\u{9FFF}' => 0.9,
+        // General punctuation, such as quotes and ellipses
+        '\u{2000}'..='\u{206F}' => 0.9,
+        // Accented Latin letters and Hangul
+        '\u{00A0}'..='\u{024F}' | '\u{AC00}'..='\u{D7A3}' => 0.8,
+        c if super::is_text(c) => 0.3,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top(bytes: &[u8]) -> Option<Encoding> {
+        detect(bytes).first().map(|candidate| candidate.encoding)
+    }
+
+    #[test]
+    fn samples() {
+        let samples = [
+            (Encoding::ShiftJis, "「おはようございます」と彼女は言った。"),
+            (Encoding::EucJp, "「おはようございます」と彼女は言った。"),
+            (Encoding::Gbk, "他说：“我们明天去北京吧。”"),
+            (Encoding::Big5, "他說：「我們明天去臺北吧。」"),
+            (Encoding::Utf8, "Chapter 1：「始まりの朝」"),
+            (Encoding::Utf16Le, "Chapter 1：「始まりの朝」"),
+        ];
+
+        for (encoding, text) in samples {
+            let mut bytes = encoding.encode(text).unwrap();
+            assert_eq!(top(&bytes), Some(encoding), "{text} in {encoding}");
+
+            // Padding after the terminator does not change the ranking
+            bytes.resize(bytes.len() + 16, 0);
+            assert_eq!(top(&bytes), Some(encoding), "{text} in {encoding}, padded");
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let candidates = detect(b"Press any key to continue\0\0\0\0");
+        assert_eq!(candidates[0].encoding, Encoding::Ascii);
+        assert_eq!(candidates[0].score, 1.0);
+
+        // The encodings that extend ASCII tie with it and keep the order of `Encoding::ALL`
+        let tied = candidates
+            .iter()
+            .take_while(|candidate| candidate.score == 1.0)
+            .map(|candidate| candidate.encoding)
+            .collect::<Vec<_>>();
+        let extending = Encoding::ALL
+            .into_iter()
+            .filter(|&encoding| encoding == Encoding::Ascii || encoding.extends_ascii())
+            .collect::<Vec<_>>();
+        assert_eq!(tied, extending);
+
+        // Read as UTF-16, the bytes pair up into unlikely characters
+        assert!(candidates
+            .iter()
+            .filter(|candidate| candidate.encoding.unit_size() == 2)
+            .all(|candidate| candidate.score < 1.0));
+    }
+
+    #[test]
+    fn ascii_as_utf16() {
+        let bytes = Encoding::Utf16Le.encode("Press any key\0").unwrap();
+        assert_eq!(top(&bytes), Some(Encoding::Utf16Le));
+    }
+
+    #[test]
+    fn empty() {
+        assert!(detect(&[]).is_empty());
+        assert!(detect(&[0; 8]).is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+#![allow(dead_code)]
+
+use std::fmt;
+
+use encoding_rs::{DecoderResult, BIG5, EUC_JP, GBK, SHIFT_JIS};
+
+pub mod detect;
+
+/// A character encoding that text may be stored with in the memory of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Shift-JIS as extended by Windows, also known as CP932
+    ShiftJis,
+    EucJp,
+    /// GBK as extended by Windows, also known as CP936
+    Gbk,
+    Big5,
+}
+
+/// The result of decoding the first character of a byte sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoded {
+    /// A character and the number of bytes it takes up
+    Char(char, usize),
+    /// The bytes end in the middle of a character
+    Incomplete,
+    /// The bytes do not start with a valid character
+    Invalid,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 8] = [
+        Encoding::Ascii,
+        Encoding::Utf8,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+        Encoding::ShiftJis,
+        Encoding::EucJp,
+        Encoding::Gbk,
+        Encoding::Big5,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ASCII",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::ShiftJis => "Shift-JIS",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::Gbk => "GBK",
+            Encoding::Big5 => "Big5",
+        }
+    }
+
+    /// Returns the size of a code unit, which characters are aligned to
+    pub fn unit_size(self) -> usize {
+        match self {
+            Encoding::Utf16Le | Encoding::Utf16Be => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns whether ASCII text is stored the same way in this encoding, which makes it likely
+    /// to be mistaken for it
+    pub fn extends_ascii(self) -> bool {
+        !matches!(
+            self,
+            Encoding::Ascii | Encoding::Utf16Le | Encoding::Utf16Be
+        )
+    }
+
+    /// Reads a UTF-16 code unit in the byte order of the encoding
+    fn utf16_unit(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Encoding::Utf16Be => u16::from_be_bytes(bytes),
+            _ => u16::from_le_bytes(bytes),
+        }
+    }
+
+    /// Returns the decoder for the legacy multi-byte encodings
+    fn codec(self) -> Option<&'static encoding_rs::Encoding> {
+        match self {
+            Encoding::ShiftJis => Some(SHIFT_JIS),
+            Encoding::EucJp => Some(EUC_JP),
+            Encoding::Gbk => Some(GBK),
+            Encoding::Big5 => Some(BIG5),
+            _ => None,
+        }
+    }
+
+    /// Decodes the bytes if all of them are valid in the encoding
+    pub fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Ascii => bytes
+                .is_ascii()
+                .then(|| bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Utf8 => std::str::from_utf8(bytes).ok().map(str::to_string),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return None;
+                }
+
+                let units = bytes
+                    .chunks_exact(2)
+                    .map(|unit| self.utf16_unit([unit[0], unit[1]]));
+                char::decode_utf16(units).collect::<Result<_, _>>().ok()
+            }
+            _ => self
+                .codec()?
+                .decode_without_bom_handling_and_without_replacement(bytes)
+                .map(String::from),
+        }
+    }
+
+    /// Decodes the bytes, replacing invalid sequences with U+FFFD
+    pub fn decode_lossy(self, bytes: &[u8]) -> String {
+        if let Some(codec) = self.codec() {
+            return codec.decode_without_bom_handling(bytes).0.into_owned();
+        }
+
+        match self {
+            Encoding::Ascii => bytes
+                .iter()
+                .map(|&b| match b.is_ascii() {
+                    true => b as char,
+                    false => char::REPLACEMENT_CHARACTER,
+                })
+                .collect(),
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            _ => {
+                let units = bytes
+                    .chunks_exact(2)
+                    .map(|unit| self.utf16_unit([unit[0], unit[1]]));
+
+                let mut text = char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect::<String>();
+
+                // A dangling byte is half of a code unit
+                if !bytes.len().is_multiple_of(2) {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                }
+
+                text
+            }
+        }
+    }
+
+    /// Encodes text, unless it contains characters the encoding can not represent
+    pub fn encode(self, text: &str) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Ascii => text.is_ascii().then(|| text.as_bytes().to_vec()),
+            Encoding::Utf8 => Some(text.as_bytes().to_vec()),
+            Encoding::Utf16Le => Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            _ => match self.codec()?.encode(text) {
+                (_, _, true) => None,
+                (bytes, _, false) => Some(bytes.into_owned()),
+            },
+        }
+    }
+
+    /// Decodes the first character of `bytes`
+    pub fn decode_char(self, bytes: &[u8]) -> Decoded {
+        let Some(&first) = bytes.first() else {
+            return Decoded::Incomplete;
+        };
+
+        match self {
+            Encoding::Ascii if first.is_ascii() => Decoded::Char(first as char, 1),
+            Encoding::Ascii => Decoded::Invalid,
+            Encoding::Utf8 => decode_utf8_char(bytes),
+            Encoding::Utf16Le | Encoding::Utf16Be => decode_utf16_char(self, bytes),
+            _ => decode_legacy_char(self, bytes),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Returns whether a character is likely to be part of text rather than binary data
+pub fn is_text(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        // Private use, and the specials such as the replacement character
+        '\u{E000}'..='\u{F8FF}' | '\u{FFF0}'..='\u{FFFF}' => false,
+        _ => !c.is_control(),
+    }
+}
+
+fn decode_utf8_char(bytes: &[u8]) -> Decoded {
+    let len = match bytes[0] {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return Decoded::Invalid,
+    };
+
+    match std::str::from_utf8(&bytes[..len.min(bytes.len())]) {
+        Ok(s) if s.len() == len => s
+            .chars()
+            .next()
+            .map_or(Decoded::Invalid, |c| Decoded::Char(c, len)),
+        Ok(_) => Decoded::Incomplete,
+        // Every byte we have is valid so far, but the character is cut off
+        Err(e) if e.error_len().is_none() => Decoded::Incomplete,
+        Err(_) => Decoded::Invalid,
+    }
+}
+
+fn decode_utf16_char(encoding: Encoding, bytes: &[u8]) -> Decoded {
+    let unit = |i: usize| {
+        bytes
+            .get(i * 2..i * 2 + 2)
+            .map(|unit| encoding.utf16_unit([unit[0], unit[1]]))
+    };
+
+    let Some(first) = unit(0) else {
+        return Decoded::Incomplete;
+    };
+
+    let (units, len) = match first {
+        0xD800..=0xDBFF => match unit(1) {
+            Some(second) => ([first, second], 4),
+            None => return Decoded::Incomplete,
+        },
+        _ => ([first, 0], 2),
+    };
+
+    match char::decode_utf16(units).next() {
+        Some(Ok(c)) => Decoded::Char(c, len),
+        _ => Decoded::Invalid,
+    }
+}
+
+fn decode_legacy_char(encoding: Encoding, bytes: &[u8]) -> Decoded {
+    let (first, second) = (bytes[0], bytes.get(1).copied());
+
+    let len = match (encoding, first) {
+        (_, 0x00..=0x7F) => return Decoded::Char(first as char, 1),
+        // Half-width katakana
+        (Encoding::ShiftJis, 0xA1..=0xDF) => 1,
+        (Encoding::ShiftJis, 0x81..=0x9F | 0xE0..=0xFC) => 2,
+        // JIS X 0212 takes up three bytes
+        (Encoding::EucJp, 0x8F) => 3,
+        (Encoding::EucJp, 0x8E | 0xA1..=0xFE) => 2,
+        // The euro sign
+        (Encoding::Gbk, 0x80) => 1,
+        // GB 18030 extends GBK with four byte sequences, which have a digit as the second byte
+        (Encoding::Gbk, 0x81..=0xFE) if second.is_some_and(|b| b.is_ascii_digit()) => 4,
+        (Encoding::Gbk | Encoding::Big5, 0x81..=0xFE) => 2,
+        _ => return Decoded::Invalid,
+    };
+
+    if bytes.len() < len {
+        return Decoded::Incomplete;
+    }
+
+    let Some(codec) = encoding.codec() else {
+        return Decoded::Invalid;
+    };
+
+    let mut decoder = codec.new_decoder_without_bom_handling();
+    // Big5 maps a few sequences to two characters, which we do not count as one
+    let mut buf = [0; 8];
+    let (result, read, written) =
+        decoder.decode_to_utf8_without_replacement(&bytes[..len], &mut buf, true);
+
+    let mut chars = std::str::from_utf8(&buf[..written])
+        .unwrap_or_default()
+        .chars();
+    match (result, chars.next(), chars.next()) {
+        (DecoderResult::InputEmpty, Some(c), None) if read == len => Decoded::Char(c, len),
+        _ => Decoded::Invalid,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits bytes into characters with `decode_char`
+    fn chars(encoding: Encoding, bytes: &[u8]) -> Vec<(char, usize)> {
+        let mut chars = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            match encoding.decode_char(&bytes[offset..]) {
+                Decoded::Char(c, len) => {
+                    chars.push((c, len));
+                    offset += len;
+                }
+                decoded => panic!("{decoded:?} at {offset} in {encoding}"),
+            }
+        }
+        chars
+    }
+
+    #[test]
+    fn round_trip() {
+        let samples = [
+            (
+                Encoding::EucJp,
+                "A「ｶﾀｶﾅ」と漢字",
+                [1, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+            ),
+            (
+                Encoding::Gbk,
+                "A“中文”€测试，好",
+                [1, 2, 2, 2, 2, 1, 2, 2, 2, 2],
+            ),
+            (
+                Encoding::Big5,
+                "A「中文」測試，臺灣",
+                [1, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+            ),
+        ];
+
+        for (encoding, text, lengths) in samples {
+            let bytes = encoding.encode(text).unwrap();
+            assert_eq!(encoding.decode(&bytes).as_deref(), Some(text), "{encoding}");
+            assert_eq!(encoding.decode_lossy(&bytes), text, "{encoding}");
+
+            let chars = chars(encoding, &bytes);
+            assert_eq!(chars.iter().map(|&(c, _)| c).collect::<String>(), text);
+            assert_eq!(
+                chars.iter().map(|&(_, len)| len).collect::<Vec<_>>(),
+                lengths
+            );
+        }
+    }
+
+    #[test]
+    fn three_byte_euc_jp() {
+        // JIS X 0212 is only ever decoded, as encoders stick to JIS X 0208
+        let bytes = [b'A', 0x8F, 0xB0, 0xA1, b'B'];
+        assert_eq!(Encoding::EucJp.decode(&bytes).as_deref(), Some("A丂B"));
+        assert_eq!(
+            chars(Encoding::EucJp, &bytes),
+            [('A', 1), ('丂', 3), ('B', 1)]
+        );
+        assert_eq!(Encoding::EucJp.encode("丂"), None);
+    }
+
+    #[test]
+    fn unencodable() {
+        assert_eq!(Encoding::Ascii.encode("café"), None);
+        assert_eq!(Encoding::EucJp.encode("한국어"), None);
+        assert_eq!(Encoding::Big5.encode("简体"), None);
+    }
+
+    #[test]
+    fn invalid_sequences() {
+        let samples: [(Encoding, &[u8]); 5] = [
+            // A lead byte followed by a byte that can not trail it
+            (Encoding::EucJp, &[b'a', 0xA4, b' ']),
+            (Encoding::ShiftJis, &[b'a', 0x82, b' ']),
+            (Encoding::Big5, &[b'a', 0xA4, b' ']),
+            (Encoding::Gbk, &[b'a', 0xFF, b' ']),
+            (Encoding::Utf8, &[b'a', 0xE3, b' ']),
+        ];
+
+        for (encoding, bytes) in samples {
+            assert_eq!(encoding.decode(bytes), None, "{encoding}");
+            assert_eq!(
+                encoding.decode_char(&bytes[1..]),
+                Decoded::Invalid,
+                "{encoding}"
+            );
+            // The space is kept rather than swallowed by the lead byte
+            assert_eq!(encoding.decode_lossy(bytes), "a\u{FFFD} ", "{encoding}");
+        }
+    }
+
+    #[test]
+    fn incomplete_sequences() {
+        let bytes = [0x8F, 0xB0, 0xA1];
+        for len in 0..3 {
+            assert_eq!(
+                Encoding::EucJp.decode_char(&bytes[..len]),
+                Decoded::Incomplete
+            );
+        }
+
+        let bytes = Encoding::Gbk.encode("中").unwrap();
+        assert_eq!(Encoding::Gbk.decode_char(&bytes[..1]), Decoded::Incomplete);
+        assert_eq!(Encoding::Gbk.decode(&bytes[..1]), None);
+        assert_eq!(Encoding::Gbk.decode_lossy(&bytes[..1]), "\u{FFFD}");
+    }
+
+    #[test]
+    fn unit_size() {
+        for encoding in Encoding::ALL {
+            let expected = match encoding {
+                Encoding::Utf16Le | Encoding::Utf16Be => 2,
+                _ => 1,
+            };
+            assert_eq!(encoding.unit_size(), expected, "{encoding}");
+        }
+
+        for encoding in [Encoding::Utf16Le, Encoding::Utf16Be] {
+            let mut bytes = encoding.encode("ab").unwrap();
+            assert_eq!(chars(encoding, &bytes), [('a', 2), ('b', 2)]);
+
+            // A dangling byte is half of a code unit
+            bytes.push(b'c');
+            assert_eq!(encoding.decode(&bytes), None);
+            assert_eq!(encoding.decode_lossy(&bytes), "ab\u{FFFD}");
+            assert_eq!(encoding.decode_char(&bytes[4..]), Decoded::Incomplete);
+
+            // Reading from the middle of a code unit pairs up the wrong bytes
+            assert_ne!(encoding.decode_lossy(&bytes[1..]), "b\u{FFFD}");
+        }
+    }
+
+    #[test]
+    fn surrogates() {
+        let bytes = Encoding::Utf16Le.encode("𠮷").unwrap();
+        assert_eq!(
+            Encoding::Utf16Le.decode_char(&bytes),
+            Decoded::Char('𠮷', 4)
+        );
+        assert_eq!(
+            Encoding::Utf16Le.decode_char(&bytes[..2]),
+            Decoded::Incomplete
+        );
+        // A low surrogate on its own
+        assert_eq!(Encoding::Utf16Le.decode_char(&bytes[2..]), Decoded::Invalid);
+        assert_eq!(Encoding::Utf16Le.decode_lossy(&bytes[2..]), "\u{FFFD}");
+    }
+}
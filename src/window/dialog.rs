@@ -30,7 +30,7 @@ use windows::{
 
 use crate::{
     id::{IDC_DIALOG_CANCEL, IDC_DIALOG_OK},
-    memory::{FileInfoField, Process, ProcessArchitecture, ProcessSnapshot},
+    memory::{FileInfoField, MemorySource, Process, ProcessArchitecture, ProcessSnapshot},
     string::INFO_TEXT,
     util::{self, get_default_font, get_text_size_wrap},
 };
@@ -458,7 +458,7 @@ impl Dialog {
                         let (proc_hicon, proc_desc, proc_name, proc_arch) = if let Ok(proc) =
                             Process::__tmp_open_ro(process.process_id())
                         {
-                            let hicon = proc.icon().unwrap();
+                            let hicon = proc.icon().ok().flatten();
                             let arch = proc.arch().unwrap();
 
                             let (proc_name, proc_desc) = if let Ok(descs) = proc.file_descriptions()
@@ -467,17 +467,19 @@ impl Dialog {
                                     .iter()
                                     .map(|desc| desc.get_string(FileInfoField::ProductName))
                                     .next()
+                                    .flatten()
+                                    .map(util::string_to_hstring)
                                     .transpose()
-                                    .unwrap()
-                                    .flatten();
+                                    .unwrap();
 
                                 let proc_desc = descs
                                     .iter()
                                     .map(|desc| desc.get_string(FileInfoField::FileDescription))
                                     .next()
+                                    .flatten()
+                                    .map(util::string_to_hstring)
                                     .transpose()
-                                    .unwrap()
-                                    .flatten();
+                                    .unwrap();
 
                                 (proc_name, proc_desc)
                             } else {
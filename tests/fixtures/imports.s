@@ -0,0 +1,27 @@
+# A DLL that imports from target.dll and other.dll, by name and by ordinal, and exports by name,
+# by ordinal and by forwarding to target.dll. Built with:
+#
+# llvm-mc -triple x86_64-pc-windows-msvc -filetype=obj -o imports.obj imports.s
+# llvm-mc -triple x86_64-pc-windows-msvc -filetype=obj -o target.obj target.s
+# llvm-mc -triple x86_64-pc-windows-msvc -filetype=obj -o other.obj other.s
+# lld-link /dll /noentry /nodefaultlib /def:target.def /out:target.dll target.obj
+# lld-link /dll /noentry /nodefaultlib /export:Other /out:other.dll other.obj
+# lld-link /dll /noentry /nodefaultlib /filealign:512 /def:imports.def /out:imports.dll \
+#     imports.obj target.lib other.lib
+#
+# Only imports.dll is checked in, and the link time stamped into its headers is the only thing
+# that differs between builds.
+
+.text
+.globl render_text
+render_text:
+  call *__imp_Target(%rip)
+  call *__imp_Hidden(%rip)
+  jmp *__imp_Other(%rip)
+.globl by_ordinal
+by_ordinal:
+  ret
+.globl _DllMainCRTStartup
+_DllMainCRTStartup:
+  mov $1, %eax
+  ret
@@ -0,0 +1,6 @@
+# The code of other.dll, which is built as described at the top of imports.s
+
+.text
+.globl Other
+Other:
+  ret
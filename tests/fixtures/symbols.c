@@ -0,0 +1,23 @@
+/*
+ * A shared library whose symbols are split between .dynsym and a separate debug file.
+ *
+ * gcc -shared -fPIC -O1 -nostdlib -Wl,-z,max-page-size=0x1000 -Wl,-z,noseparate-code \
+ *     -Wl,-Ttext-segment=0x10000 -Wl,--build-id=none -o libsymbols.full.so symbols.c
+ * objcopy --only-keep-debug libsymbols.full.so libsymbols.so.debug
+ * objcopy --strip-all --add-gnu-debuglink=libsymbols.so.debug libsymbols.full.so libsymbols.so
+ *
+ * The text segment is linked at 0x10000, so symbols are not at their offset from the base.
+ */
+
+int shown_lines = 3;
+
+/* Only in .symtab, which is moved to the debug file */
+__attribute__((noinline, used)) static int count_line(int line)
+{
+    return shown_lines + line;
+}
+
+int show_line(int line)
+{
+    return count_line(line) * 2;
+}
@@ -0,0 +1,9 @@
+# The code of target.dll, which is built as described at the top of imports.s
+
+.text
+.globl Target
+Target:
+  ret
+.globl Hidden
+Hidden:
+  ret
@@ -0,0 +1,7 @@
+# The code of version.dll, which is built as described at the top of version.rc
+
+.text
+.globl _DllMainCRTStartup
+_DllMainCRTStartup:
+  mov $1, %eax
+  ret